@@ -0,0 +1,464 @@
+// Generated by `cargo run --bin gen_dts` from the Rust types. Do not edit by hand.
+
+export type AfterHalfAcceptanceMessage = {
+    dest?: Coord | null;
+    type: "AfterHalfAcceptance";
+};
+
+export type AfterHalfAcceptanceMessageStruct = {
+    message: AfterHalfAcceptanceMessage;
+};
+
+/** How hard the bot plays; chosen on entering a game against it, and kept for the whole game */
+export type BotDifficulty = "easy" | "normal" | "hard" | "engine";
+
+export type ChatContent = {
+    text: string;
+    type: "Text";
+} | {
+    phrase: QuickPhrase;
+    type: "QuickPhrase";
+};
+
+export type ChatContentStruct = {
+    message: ChatContent;
+};
+
+export type ChatMessage = {
+    by_IA_owner: boolean;
+    content: ChatContent;
+    /** Position of this message in the room's chat log; pass it back as `since` when polling. */
+    id: number;
+    /** Seconds since the UNIX epoch */
+    sent_at: number;
+};
+
+export type Ciurl = [boolean, boolean, boolean, boolean, boolean];
+
+export type Color = 0 | 1;
+
+export type Coord = ["A" | "E" | "I" | "U" | "O" | "Y" | "AI" | "AU" | "IA", "K" | "L" | "N" | "T" | "Z" | "X" | "C" | "M" | "P"];
+
+/** Stable, machine-readable reason why a request was refused. The frontend should branch on this rather than on `why_illegal`, which is meant for humans. */
+export type ErrorCode = "unparsable_token" | "unknown_token" | "read_only_token" | "invalid_token" | "expired_token" | "revoked_token" | "unparsable_room_id" | "unknown_room" | "not_your_turn" | "wrong_phase" | "illegal_move" | "illegal_move_kind" | "no_hand_to_resolve" | "game_over" | "invalid_chat_message" | "rate_limited" | "invalid_request" | "internal_error";
+
+export type FinalResult = {
+    dest: Coord;
+    thwarted_by_failing_water_entry_ciurl?: Ciurl | null;
+    water_entry_ciurl?: Ciurl | null;
+};
+
+export type HandCompletionStatus = "TyMok" | "TaXot" | "NotYetDetermined";
+
+export type LiveRoom = {
+    A_owner_s_score: number;
+    IA_owner_s_score: number;
+    elapsed_secs: number;
+    move_count: number;
+    opponent_is_bot: boolean;
+    room_id: RoomId;
+    season: number;
+};
+
+export type MainMessage = {
+    plannedDirection: Coord;
+    src: Coord;
+    step: Coord;
+    type: "InfAfterStep";
+} | {
+    data: NonTamMoveDotData;
+    type: "NonTamMove";
+} | {
+    type: "TamMove";
+} & ({
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    stepStyle: "NoStep";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringFormer";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringLatter";
+});
+
+export type MainMessageStruct = {
+    message: MainMessage;
+};
+
+export type MoveToBePolled = {
+    data: NonTamMoveDotData;
+    type: "NonTamMove";
+} | {
+    type: "TamMove";
+} & ({
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    stepStyle: "NoStep";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringFormer";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringLatter";
+}) | {
+    finalResult?: FinalResult | null;
+    plannedDirection: Coord;
+    src: Coord;
+    step: Coord;
+    stepping_ciurl: Ciurl;
+    type: "InfAfterStep";
+};
+
+export type MsgChatPoll = {
+    since: number;
+};
+
+/** The body of `/matching/random/entry`, which may as well be left out */
+export type MsgRandomEntry = {
+    /** Keeps the room out of `/rooms/live` and away from spectators from the start. The room is unlisted if either player asks for it. */
+    unlisted?: boolean;
+};
+
+/** The body of `/matching/vs_cpu/entry`, which may as well be left out */
+export type MsgVsCpuEntry = {
+    difficulty?: BotDifficulty;
+    /** Keeps the room out of `/rooms/live` and away from spectators from the start */
+    unlisted?: boolean;
+};
+
+export type MsgWithAccessToken = {
+    access_token: string;
+};
+
+export type MsgWithRoomId = {
+    room_id: string;
+};
+
+export type NonTamMoveDotData = {
+    color: Color;
+    dest: Coord;
+    profession: Profession;
+    type: "FromHand";
+} | {
+    dest: Coord;
+    src: Coord;
+    type: "SrcDst";
+    water_entry_ciurl?: Ciurl | null;
+} | {
+    dest: Coord;
+    src: Coord;
+    step: Coord;
+    type: "SrcStepDstFinite";
+    water_entry_ciurl?: Ciurl | null;
+};
+
+export type PieceInHand = {
+    color: Color;
+    profession: Profession;
+};
+
+export type PieceOnBoard = {
+    coord: Coord;
+    piece: PieceWithPerspective;
+};
+
+/** A piece on the board, as seen from one of the players. */
+export type PieceWithPerspective = {
+    type: "Tam2";
+} | {
+    color: Color;
+    is_mine: boolean;
+    profession: Profession;
+    type: "NonTam2Piece";
+};
+
+export type Profession = 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9;
+
+export type QuickPhrase = "greetings" | "good_luck" | "good_game" | "well_played" | "thanks" | "sorry" | "please_wait" | "oops";
+
+export type RetAfterHalfAcceptance = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    ciurl: Ciurl;
+    type: "WithWaterEntry";
+} | {
+    type: "WithoutWaterEntry";
+};
+
+export type RetChatPoll = {
+    messages: ChatMessage[];
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetGameRecord = {
+    chat: ChatMessage[];
+    seasons: SpectatedSeason[];
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetInfAfterStep = {
+    ciurl: Ciurl;
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetInfPoll = {
+    content: MoveToBePolled;
+    type: "MoveMade";
+} | {
+    type: "NotYetDetermined";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetLegalMoves = {
+    candidates: MainMessage[];
+    hop1zuo1_candidates: MainMessage[];
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetLiveRooms = {
+    rooms: LiveRoom[];
+    type: "Ok";
+};
+
+export type RetMainPoll = {
+    content: MoveToBePolled;
+    /** `message` rendered in the negotiated language */
+    localized_message?: string | null;
+    message?: TacticsKey | null;
+    type: "MoveMade";
+} | {
+    type: "NotYetDetermined";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetNormalMove = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    ciurl: Ciurl;
+    type: "WithWaterEntry";
+} | {
+    type: "WithoutWaterEntry";
+};
+
+export type RetRandomCancel = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    cancellable: boolean;
+    type: "Ok";
+};
+
+export type RetRandomEntry = {
+    access_token: string;
+    type: "InWaitingList";
+} | {
+    access_token: string;
+    is_IA_down_for_me: boolean;
+    is_first_move_my_move: WhoGoesFirst;
+    type: "LetTheGameBegin";
+};
+
+export type RetRandomPoll = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    ret: RetRandomEntry;
+    type: "Ok";
+};
+
+export type RetRoomState = {
+    state: RoomState;
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetSendChat = {
+    id: number;
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetSpectateEntry = {
+    spectator_token: string;
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetSpectatorPoll = {
+    current_season: number;
+    /** One entry per season played so far, including the current one. */
+    seasons: SpectatedSeason[];
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetTaXot = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    is_first_move_my_move?: WhoGoesFirst | null;
+    type: "Ok";
+};
+
+export type RetTyMok = {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+} | {
+    type: "Ok";
+};
+
+export type RetUnlistRoom = {
+    type: "Ok";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetValidate = {
+    /** Whether the stepping ciurl would be cast (only for `InfAfterStep`) */
+    stepping_ciurl_required: boolean;
+    type: "Legal";
+    /** Whether the water entry ciurl would be cast. For an `InfAfterStep`, `None`, since where the piece ends up is not known until the stepping ciurl is cast */
+    water_entry_ciurl_required?: boolean | null;
+} | {
+    code: ErrorCode;
+    type: "Illegal";
+    why_illegal: string;
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RetVsCpuEntry = {
+    access_token: string;
+    is_IA_down_for_me: boolean;
+    is_first_move_my_move: WhoGoesFirst;
+    type: "LetTheGameBegin";
+};
+
+export type RetWhetherTyMokPoll = {
+    type: "TyMok";
+} | {
+    is_first_move_my_move?: WhoGoesFirst | null;
+    type: "TaXot";
+} | {
+    type: "NotYetDetermined";
+} | {
+    code: ErrorCode;
+    type: "Err";
+    why_illegal: string;
+};
+
+export type RoomId = string;
+
+/** Everything a client needs to redraw the game from scratch, e.g. after a reload. Coordinates stay absolute; `is_ia_down_for_me` tells the client how to orient them. */
+export type RoomState = {
+    board: PieceOnBoard[];
+    is_IA_down_for_me: boolean;
+    is_my_turn: boolean;
+    my_hop1zuo1: PieceInHand[];
+    my_score: number;
+    opponent_hop1zuo1: PieceInHand[];
+    opponent_score: number;
+    /** Present while the stepping ciurl has been cast but the destination is not yet decided */
+    pending_inf_after_step?: MoveToBePolled | null;
+    rate: number;
+    season: number;
+};
+
+export type SpectatedMove = {
+    by_IA_owner: boolean;
+    content: MoveToBePolled;
+    /** Whether the player chose ty mok or ta xot after completing a hand with this move */
+    status?: HandCompletionStatus | null;
+};
+
+export type SpectatedSeason = {
+    is_first_move_IA_move?: WhoGoesFirst | null;
+    moves: SpectatedMove[];
+};
+
+export type TacticsKey = "victory_almost_certain" | "strengthened_shaman" | "free_lunch" | "avoid_defeat" | "loss_almost_certain" | "neutral";
+
+export type TamMoveInternal = {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    stepStyle: "NoStep";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringFormer";
+} | {
+    firstDest: Coord;
+    secondDest: Coord;
+    src: Coord;
+    step: Coord;
+    stepStyle: "StepsDuringLatter";
+};
+
+export type WhoGoesFirst = {
+    process: Ciurl[][];
+    result: boolean;
+};
@@ -0,0 +1,9 @@
+//! Writes the TypeScript definitions of the wire types; see `cerke_online_backend_rewritten::typescript`.
+
+use cerke_online_backend_rewritten::typescript::{generate, DTS_PATH};
+
+fn main() -> std::io::Result<()> {
+    std::fs::write(DTS_PATH, generate())?;
+    println!("wrote {DTS_PATH}");
+    Ok(())
+}
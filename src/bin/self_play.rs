@@ -0,0 +1,218 @@
+//! Pits two bots against each other for a number of full games, and reports how each of them fared.
+//! Every move goes through `GameState` as in a game on the server, so that a bot or a state transition
+//! that panics or rejects a move shows up here rather than in a player's game.
+//!
+//! ```text
+//! cargo run --release --bin self_play -- <bot> <bot> [games]
+//! ```
+//!
+//! A bot is named by its difficulty: `easy`, `normal`, `hard` or `engine`, set up from the environment as on the server.
+//! The two bots take turns playing the IA side.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use cerke_online_backend_rewritten::bot::tymok::victory_of;
+use cerke_online_backend_rewritten::bot::{bot_for, play_turn, BotSettings};
+use cerke_online_backend_rewritten::types::bot::BotDifficulty;
+use cerke_online_backend_rewritten::types::{GameState, Phase};
+use cetkaik_calculate_hand::calculate_hands_and_score_from_pieces;
+use cetkaik_core::absolute::Side;
+use cetkaik_full_state_transition::{
+    resolve,
+    state::{HandNotResolved, HandResolved},
+    Config, IfTaxot, Victor,
+};
+
+const DEFAULT_GAMES: usize = 10;
+
+/// A game still going after this many moves is abandoned, so that two bots shuffling their pieces back and forth do not hang the run
+const MAX_MOVES: usize = 2000;
+
+enum Outcome {
+    Finished { winner: Option<Side>, ia_score: i32 },
+    Abandoned,
+    Crashed { side: Side, why: String },
+}
+
+#[derive(Default)]
+struct Tally {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    finished: usize,
+    score_total: i32,
+    crashes: usize,
+}
+
+fn side_of(victor: Victor) -> Option<Side> {
+    [Side::IASide, Side::ASide]
+        .into_iter()
+        .find(|side| victory_of(*side) == Some(victor))
+}
+
+/// The IA side's score once the final hand has been banked; a game won by points ends at 40 to 0
+fn final_ia_score(state: &HandNotResolved, winner: Option<Side>) -> i32 {
+    let by_points = match winner {
+        Some(Side::IASide) => 40,
+        Some(Side::ASide) => 0,
+        None => 20,
+    };
+    let hop1zuo1 = match state.whose_turn {
+        Side::IASide => &state.f.ia_side_hop1zuo1,
+        Side::ASide => &state.f.a_side_hop1zuo1,
+    };
+    let hand = calculate_hands_and_score_from_pieces(hop1zuo1).map_or(0, |answer| answer.score);
+    state
+        .scores
+        .edit(hand, state.whose_turn, state.rate)
+        .map_or(by_points, |scores| scores.ia())
+}
+
+/// Plays one game, with `bots[0]` on the IA side. Returns the outcome and how many moves were made.
+fn play_game(bots: [BotDifficulty; 2], settings: &BotSettings) -> (Outcome, usize) {
+    let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+    let mut game_state = GameState::new(initial_state, Config::cerke_online_alpha());
+    for moves in 0..MAX_MOVES {
+        match &game_state.state {
+            Phase::Moved(state) if game_state.is_game_over() => {
+                let outcome = match resolve(state, game_state.config) {
+                    HandResolved::GameEndsWithoutTymokTaxot(victor) => {
+                        let winner = side_of(victor);
+                        Outcome::Finished {
+                            winner,
+                            ia_score: if winner == Some(Side::IASide) { 40 } else { 0 },
+                        }
+                    }
+                    HandResolved::HandExists {
+                        if_taxot: IfTaxot::VictoriousSide(victor),
+                        ..
+                    } => {
+                        let winner = side_of(victor);
+                        Outcome::Finished {
+                            winner,
+                            ia_score: final_ia_score(state, winner),
+                        }
+                    }
+                    HandResolved::HandExists { .. } | HandResolved::NeitherTymokNorTaxot(_) => {
+                        unreachable!("`is_game_over` holds")
+                    }
+                };
+                return (outcome, moves);
+            }
+            Phase::Start(_) => {}
+            phase => {
+                let why = format!("the game was left in the {} phase", phase.phase_name());
+                let side = phase.whose_turn();
+                return (Outcome::Crashed { side, why }, moves);
+            }
+        }
+
+        let side = game_state.state.whose_turn();
+        let difficulty = if side == Side::IASide {
+            bots[0]
+        } else {
+            bots[1]
+        };
+        // a new bot every turn, as on the server
+        let bot = bot_for(difficulty, settings);
+        let why = match catch_unwind(AssertUnwindSafe(|| {
+            play_turn(bot.as_ref(), &mut game_state)
+        })) {
+            Ok(Ok(_)) => continue,
+            Ok(Err(e)) => e.why_illegal,
+            Err(panic) => panic
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "a panic".to_string()),
+        };
+        return (Outcome::Crashed { side, why }, moves);
+    }
+    (Outcome::Abandoned, MAX_MOVES)
+}
+
+fn parse_bot(name: &str) -> Result<BotDifficulty, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| format!("unknown bot `{name}`; expected easy, normal, hard or engine"))
+}
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [first, second, rest @ ..] = args.as_slice() else {
+        return Err("usage: self_play <bot> <bot> [games]".to_string());
+    };
+    let bots = [parse_bot(first)?, parse_bot(second)?];
+    let games = match rest.first() {
+        Some(games) => games
+            .parse()
+            .map_err(|_| format!("`{games}` is not a number of games"))?,
+        None => DEFAULT_GAMES,
+    };
+    let settings = BotSettings::from_env();
+
+    let mut tallies = [Tally::default(), Tally::default()];
+    let mut total_moves = 0;
+    let mut abandoned = 0;
+    for game in 0..games {
+        // which of the two bots plays the IA side
+        let ia = game % 2;
+        let (outcome, moves) = play_game([bots[ia], bots[1 - ia]], &settings);
+        total_moves += moves;
+        let of = |side: Side| if side == Side::IASide { ia } else { 1 - ia };
+        match outcome {
+            Outcome::Finished { winner, ia_score } => {
+                for (i, tally) in tallies.iter_mut().enumerate() {
+                    tally.finished += 1;
+                    tally.score_total += if i == ia { ia_score } else { 40 - ia_score };
+                    match winner {
+                        Some(side) if of(side) == i => tally.wins += 1,
+                        Some(_) => tally.losses += 1,
+                        None => tally.draws += 1,
+                    }
+                }
+                println!("game {game}: {moves} moves, IA side scored {ia_score}");
+            }
+            Outcome::Abandoned => {
+                abandoned += 1;
+                println!("game {game}: abandoned after {moves} moves");
+            }
+            Outcome::Crashed { side, why } => {
+                tallies[of(side)].crashes += 1;
+                println!(
+                    "game {game}: {:?} crashed at move {moves}: {why}",
+                    bots[of(side)]
+                );
+            }
+        }
+    }
+
+    println!();
+    println!("{:?} vs {:?}, {games} games", bots[0], bots[1]);
+    println!(
+        "{:<8}{:>6}{:>7}{:>8}{:>11}{:>9}",
+        "", "wins", "draws", "losses", "avg score", "crashes"
+    );
+    for (bot, tally) in bots.iter().zip(&tallies) {
+        let average = if tally.finished == 0 {
+            0.0
+        } else {
+            f64::from(tally.score_total) / tally.finished as f64
+        };
+        println!(
+            "{:<8}{:>6}{:>7}{:>8}{:>11.1}{:>9}",
+            format!("{bot:?}"),
+            tally.wins,
+            tally.draws,
+            tally.losses,
+            average,
+            tally.crashes
+        );
+    }
+    if games > 0 {
+        println!(
+            "average length: {:.1} moves; abandoned: {abandoned}",
+            total_moves as f64 / games as f64
+        );
+    }
+    Ok(())
+}
@@ -1,100 +1,412 @@
-use crate::types::{Ciurl, GameState, MoveToBePolled , bot::*};
-use cetkaik_full_state_transition::{Config, message::{AfterHalfAcceptance, NormalMove, InfAfterStep, PureMove}, state};
-use cetkaik_core::*;
+use super::engine::{EngineBot, EngineConfig};
+use super::heuristic::HeuristicBot;
+use super::search::{SearchBot, SearchBudget};
+use super::tymok::{self, HandDecision};
+use crate::types::{
+    bot::{BotDifficulty, TacticsKey},
+    ApiError, ErrorCode, GameState, MoveToBePolled, Phase, RetAfterHalfAcceptance, RetInfAfterStep,
+    RetNormalMove, RetTaXot, RetTyMok,
+};
+use cetkaik_core::absolute;
+use cetkaik_full_state_transition::{
+    message::{AfterHalfAcceptance, InfAfterStep, NormalMove, PureMove},
+    resolve,
+    state::{self, HandResolved},
+    Config,
+};
 use rand::prelude::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
 
+/// A player that can sit opposite a human. Only the move itself has to be chosen;
+/// the decisions that follow it default to what the built-in bots do.
+pub trait Bot: Send + Sync {
+    /// `None` if there is no move to make at all.
+    fn choose_move(&self, state: &state::GroundState, config: Config)
+        -> Option<BotMoveWithTactics>;
 
-struct CerkeBot { 
-    
+    /// Where the piece of `planned` goes, now that `state.ciurl` sticks have landed face up.
+    /// By default, the planned destination if the sticks allow reaching it, and otherwise back to where it started.
+    fn choose_after_half_acceptance(
+        &self,
+        planned: InfAfterStep,
+        state: &state::ExcitedState,
+        _config: Config,
+    ) -> AfterHalfAcceptance {
+        planned_destination(planned, state.ciurl)
+    }
+
+    /// Called only when the bot's move has completed a hand.
+    fn choose_hand_decision(&self, state: &state::HandNotResolved, config: Config) -> HandDecision {
+        tymok::decide(state, config).unwrap_or(HandDecision::TaXot)
+    }
+
+    /// Asks the bot to wind up whatever it is choosing as soon as it can, since nobody is waiting for it any more.
+    /// By default, the bot is left to run to the end of its own budget.
+    fn stop(&self) {}
 }
 
+fn planned_destination(planned: InfAfterStep, ciurl: i32) -> AfterHalfAcceptance {
+    AfterHalfAcceptance {
+        dest: (absolute::distance(planned.step, planned.planned_direction) <= ciurl)
+            .then_some(planned.planned_direction),
+    }
+}
 
 pub enum BotMove {
     NormalMove(NormalMove),
-    InfAfterStep { 
-        dat: InfAfterStep, 
-        after: [AfterHalfAcceptance;6]
-    }
+    InfAfterStep {
+        dat: InfAfterStep,
+        after: [AfterHalfAcceptance; 6],
+    },
 }
 
-
 impl BotMove {
-    fn from_strict_pure_move(pure_move: &PureMove) -> Self {
+    /// An `InfAfterStep` goes to the planned destination whenever the sticks allow it, as [`Bot`] does by default.
+    pub(crate) fn from_strict_pure_move(pure_move: &PureMove) -> Self {
         match pure_move {
-            PureMove::InfAfterStep(m) => {
-                let mut after = [None; 6];
-                for i in 1..6i32 {
-                    after[i as usize] = if absolute::distance(m.src,m.planned_direction) <= i {
-                        Some(m.planned_direction)
-                    } else { 
-                        None
-                    }
-                }
-                BotMove::InfAfterStep {
-                    dat: m.clone(),
-                    after: after.map(|dest| AfterHalfAcceptance { dest })
-                }
-            },
-            PureMove::NormalMove(m) => {
-                BotMove::NormalMove(m.clone().into())
+            PureMove::InfAfterStep(m) => BotMove::InfAfterStep {
+                dat: *m,
+                after: [0, 1, 2, 3, 4, 5].map(|ciurl| planned_destination(*m, ciurl)),
             },
+            PureMove::NormalMove(m) => BotMove::NormalMove(*m),
         }
     }
 }
 
-impl From<BotMove> for MoveToBePolled {
-    fn from(bot_move: BotMove) -> Self {
-        match bot_move {
-            BotMove::NormalMove(mov) => {
-                match mov.into() {
-                    crate::types::NormalMove::NonTamMove { data } => MoveToBePolled::NonTamMove { data},
-                    crate::types::NormalMove::TamMove { flatten } => MoveToBePolled::TamMove {flatten},
+pub struct BotMoveWithTactics {
+    pub tactics: Option<TacticsKey>,
+    pub mov: PureMove,
+}
+
+/// Plays `bot`'s turn through the same state transitions as a human's, so that the sticks are cast
+/// and the move is recorded for the opponent to poll; then, if a hand has been completed, lets `bot` choose between ty mok and ta xot.
+/// Returns the move as recorded, since a ta xot moves on to the next season.
+///
+/// # Errors
+/// Returns `Err` if `bot` has no move to make, or if its move is rejected by the state transition.
+pub fn play_turn(
+    bot: &dyn Bot,
+    game_state: &mut GameState,
+) -> Result<(MoveToBePolled, Option<TacticsKey>), ApiError> {
+    let Phase::Start(state) = &game_state.state else {
+        return Err(game_state.wrong_phase_error());
+    };
+    let Some(BotMoveWithTactics { tactics, mov }) = bot.choose_move(state, game_state.config)
+    else {
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            "the bot has no move to make",
+        ));
+    };
+
+    match mov {
+        PureMove::NormalMove(mov) => {
+            if let RetNormalMove::Err(e) = game_state.apply_normal_move(mov) {
+                return Err(e);
+            }
+        }
+        PureMove::InfAfterStep(planned) => {
+            if let RetInfAfterStep::Err(e) = game_state.apply_inf_after_step(planned) {
+                return Err(e);
+            }
+            let Phase::AfterCiurl(excited) = &game_state.state else {
+                return Err(game_state.wrong_phase_error());
+            };
+            let after = bot.choose_after_half_acceptance(planned, excited, game_state.config);
+            if let RetAfterHalfAcceptance::Err(e) = game_state.apply_after_half_acceptance(after) {
+                return Err(e);
+            }
+        }
+    }
+
+    let Some(recorded) = game_state.get_last_move().map(|mov| mov.mov.clone()) else {
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            "the bot's move was not recorded",
+        ));
+    };
+    let Phase::Moved(state) = &game_state.state else {
+        return Err(ApiError::new(
+            ErrorCode::InternalError,
+            format!(
+                "the bot's move left the game in the {} phase",
+                game_state.state.phase_name()
+            ),
+        ));
+    };
+    if matches!(
+        resolve(state, game_state.config),
+        HandResolved::HandExists { .. }
+    ) {
+        match bot.choose_hand_decision(state, game_state.config) {
+            HandDecision::TaXot => {
+                if let RetTaXot::Err(e) = game_state.apply_taxot() {
+                    return Err(e);
                 }
-            },
-            BotMove::InfAfterStep { dat, after } => {
-                let InfAfterStep { src, step, planned_direction } = dat;
-                let ciurl = Ciurl::new(&mut rand::thread_rng());
-                let water_ciurl = Ciurl::new(&mut rand::thread_rng());
-
-                let dest = after[ciurl.count()].dest;
-                let final_result = match dest  {
-                    Some(dest) => Some(crate::types::FinalResult {
-                        dest,
-                        water_entry_ciurl: todo!(),
-                        thwarted_by_failing_water_entry_ciurl: todo!(),
-                    }),
-                    None => None,
-                };
-
-                MoveToBePolled::InfAfterStep{
-                    src,
-                    step,
-                    coord_signifying_planned_direction: planned_direction,
-                    stepping_ciurl: ciurl ,
-                    final_result
-                }                
-            } ,
+            }
+            HandDecision::TyMok => {
+                if let RetTyMok::Err(e) = game_state.apply_tymok() {
+                    return Err(e);
+                }
+            }
         }
+    } else {
+        game_state.apply_resolve();
     }
+    Ok((recorded, tactics))
 }
 
-pub struct BotMoveWithTactics {
-    pub tactics: TacticsKey,
-    pub bot_move: BotMove
+/// `None` if there is no move to make at all.
+#[must_use]
+pub fn bot_random(game_state: &state::GroundState, config: Config) -> Option<BotMoveWithTactics> {
+    let mut rng = rand::thread_rng();
+    let (_hop1zuo1_candidates, candidates) = game_state.get_candidates(config);
+
+    let pure_move = candidates.choose(&mut rng)?;
+    Some(BotMoveWithTactics {
+        tactics: Some(TacticsKey::Neutral),
+        mov: pure_move.clone(),
+    })
 }
 
-pub fn bot_random(game_state: &state::GroundState, config: Config) -> BotMoveWithTactics {
-    let mut rng = rand::thread_rng();
-    let (hop1zuo1_candidates,candidates) = game_state.get_candidates(config);
-    
-    let pure_move = candidates.choose(&mut rng).unwrap();
-    BotMoveWithTactics {
-        tactics: TacticsKey::Neutral,
-        bot_move: BotMove::from_strict_pure_move(pure_move)
+/// Plays as [`bot_random`] does
+pub struct RandomBot;
+
+impl Bot for RandomBot {
+    fn choose_move(
+        &self,
+        state: &state::GroundState,
+        config: Config,
+    ) -> Option<BotMoveWithTactics> {
+        bot_random(state, config)
     }
 }
 
+/// How long a bot seems to think about its move: a time picked at random between `min` and `max`,
+/// counted from when its turn came. A bot that takes longer is waited for instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThinkTime {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl ThinkTime {
+    pub const INSTANT: Self = Self {
+        min: Duration::ZERO,
+        max: Duration::ZERO,
+    };
+
+    const fn millis(min: u64, max: u64) -> Self {
+        Self {
+            min: Duration::from_millis(min),
+            max: Duration::from_millis(max),
+        }
+    }
+
+    /// Reads `var`, written as `MIN-MAX` in milliseconds, or as a single number for a fixed time.
+    /// `None` if `var` is unset or malformed.
+    fn from_env(var: &str) -> Option<Self> {
+        let value = std::env::var(var).ok()?;
+        let (min, max) = value.split_once('-').unwrap_or((&value, &value));
+        let (min, max) = (min.trim().parse().ok()?, max.trim().parse().ok()?);
+        (min <= max).then(|| Self::millis(min, max))
+    }
+
+    #[must_use]
+    pub fn sample(&self) -> Duration {
+        if self.min >= self.max {
+            return self.min;
+        }
+        rand::thread_rng().gen_range(self.min..=self.max)
+    }
+}
 
-pub fn bot_move(game_state: &state::GroundState, config: Config) -> BotMoveWithTactics {
-    bot_random(game_state, config)
-}
\ No newline at end of file
+/// How the server has set up the bots that need setting up
+#[derive(Debug, Clone)]
+pub struct BotSettings {
+    pub search: SearchBudget,
+
+    /// The external engine, if any
+    pub engine: Option<EngineConfig>,
+
+    /// How long any bot may think about a move before it is given up on
+    pub think_limit: Duration,
+
+    /// How long the bot of each difficulty seems to think, so that its move does not show up the moment the human has moved.
+    /// A difficulty that is missing moves as soon as it can.
+    pub think_times: HashMap<BotDifficulty, ThinkTime>,
+}
+
+impl Default for BotSettings {
+    fn default() -> Self {
+        Self {
+            search: SearchBudget::default(),
+            engine: None,
+            think_limit: Duration::from_secs(10),
+            think_times: HashMap::from([
+                (BotDifficulty::Easy, ThinkTime::millis(1000, 3000)),
+                (BotDifficulty::Normal, ThinkTime::millis(1500, 4000)),
+                (BotDifficulty::Hard, ThinkTime::millis(2000, 5000)),
+                (BotDifficulty::Engine, ThinkTime::millis(2000, 5000)),
+            ]),
+        }
+    }
+}
+
+impl BotSettings {
+    /// Reads the settings of each bot, `BOT_THINK_LIMIT_MILLIS`, and the think time of each difficulty
+    /// from `BOT_THINK_TIME_MILLIS_EASY`, `…_NORMAL`, `…_HARD` and `…_ENGINE`, such as `1000-3000`.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let think_limit = std::env::var("BOT_THINK_LIMIT_MILLIS")
+            .ok()
+            .and_then(|millis| millis.parse().ok())
+            .map(Duration::from_millis);
+        let mut think_times = default.think_times;
+        for (difficulty, var) in [
+            (BotDifficulty::Easy, "BOT_THINK_TIME_MILLIS_EASY"),
+            (BotDifficulty::Normal, "BOT_THINK_TIME_MILLIS_NORMAL"),
+            (BotDifficulty::Hard, "BOT_THINK_TIME_MILLIS_HARD"),
+            (BotDifficulty::Engine, "BOT_THINK_TIME_MILLIS_ENGINE"),
+        ] {
+            if let Some(think_time) = ThinkTime::from_env(var) {
+                think_times.insert(difficulty, think_time);
+            }
+        }
+        Self {
+            search: SearchBudget::from_env(),
+            engine: EngineConfig::from_env(),
+            think_limit: think_limit.unwrap_or(default.think_limit),
+            think_times,
+        }
+    }
+
+    #[must_use]
+    pub fn think_time(&self, difficulty: BotDifficulty) -> ThinkTime {
+        self.think_times
+            .get(&difficulty)
+            .copied()
+            .unwrap_or(ThinkTime::INSTANT)
+    }
+}
+
+/// The bot that plays at `difficulty`. A new bot is added by giving it a difficulty and an arm here.
+#[must_use]
+pub fn bot_for(difficulty: BotDifficulty, settings: &BotSettings) -> Box<dyn Bot> {
+    match (difficulty, &settings.engine) {
+        (BotDifficulty::Easy, _) => Box::new(RandomBot),
+        (BotDifficulty::Normal, _) | (BotDifficulty::Engine, None) => Box::new(HeuristicBot),
+        (BotDifficulty::Hard, _) => Box::new(SearchBot::new(settings.search)),
+        (BotDifficulty::Engine, Some(engine)) => Box::new(EngineBot::new(engine.clone())),
+    }
+}
+
+/// The move that the bot at the default difficulty would make; the strategies themselves are behind [`bot_for`].
+/// `None` if there is no move to make at all.
+#[must_use]
+pub fn bot_move(game_state: &state::GroundState, config: Config) -> Option<BotMoveWithTactics> {
+    bot_for(BotDifficulty::default(), &BotSettings::default()).choose_move(game_state, config)
+}
+#[cfg(test)]
+mod tests {
+    use cetkaik_core::absolute;
+    use cetkaik_full_state_transition::{message::PureMove, state::GroundState, Config};
+    use rand::prelude::SliceRandom;
+
+    use super::{play_turn, Bot, BotMoveWithTactics};
+    use crate::types::{GameState, MoveToBePolled, Phase};
+
+    /// Plays the move it is given, whatever the position
+    struct Forced(PureMove);
+
+    impl Bot for Forced {
+        fn choose_move(&self, _state: &GroundState, _config: Config) -> Option<BotMoveWithTactics> {
+            Some(BotMoveWithTactics {
+                tactics: None,
+                mov: self.0.clone(),
+            })
+        }
+    }
+
+    fn new_game() -> GameState {
+        let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+        GameState::new(initial_state, Config::cerke_online_alpha())
+    }
+
+    /// Plays random moves, and whenever an `InfAfterStep` is available, forces the bot to play one.
+    #[test]
+    fn inf_after_step_goes_through_the_state_transition() {
+        let mut rng = rand::thread_rng();
+        let mut game_state = new_game();
+        let mut inf_after_steps_played = 0;
+        while inf_after_steps_played < 100 {
+            let Phase::Start(state) = &game_state.state else {
+                game_state = new_game();
+                continue;
+            };
+            let (_, candidates) = state.get_candidates(game_state.config);
+            let inf_after_steps: Vec<_> = candidates
+                .iter()
+                .filter(|mov| matches!(mov, PureMove::InfAfterStep(_)))
+                .collect();
+
+            let Some(mov) = inf_after_steps.choose(&mut rng) else {
+                let mov = candidates.choose(&mut rng).unwrap();
+                play_turn(&Forced(mov.clone()), &mut game_state).unwrap();
+                continue;
+            };
+            let PureMove::InfAfterStep(planned) = mov else {
+                unreachable!()
+            };
+            let (recorded, _) = play_turn(&Forced((*mov).clone()), &mut game_state).unwrap();
+            inf_after_steps_played += 1;
+
+            let MoveToBePolled::InfAfterStep {
+                step,
+                coord_signifying_planned_direction,
+                stepping_ciurl,
+                final_result,
+                ..
+            } = recorded
+            else {
+                panic!("the InfAfterStep was not recorded");
+            };
+            assert_eq!(
+                planned.planned_direction,
+                coord_signifying_planned_direction
+            );
+            let reachable = absolute::distance(step, coord_signifying_planned_direction)
+                <= i32::try_from(stepping_ciurl.count()).unwrap();
+            assert_eq!(reachable, final_result.is_some());
+            if let Some(result) = final_result {
+                assert_eq!(coord_signifying_planned_direction, result.dest);
+                match (
+                    result.water_entry_ciurl,
+                    result.thwarted_by_failing_water_entry_ciurl,
+                ) {
+                    (Some(ciurl), None) => assert!(ciurl.count() >= 3),
+                    (None, Some(ciurl)) => assert!(ciurl.count() < 3),
+                    (None, None) => {}
+                    (Some(_), Some(_)) => panic!("the water entry both succeeded and failed"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bot_move_plays_a_legal_move() {
+        let game_state = new_game();
+        let Phase::Start(state) = &game_state.state else {
+            panic!("a new game starts with a move to make");
+        };
+        let (hop1zuo1_candidates, candidates) = state.get_candidates(game_state.config);
+        let chosen = super::bot_move(state, game_state.config).unwrap();
+        assert!(hop1zuo1_candidates
+            .iter()
+            .chain(&candidates)
+            .any(|mov| *mov == chosen.mov));
+    }
+}
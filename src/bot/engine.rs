@@ -0,0 +1,384 @@
+//! A bot played by an executable outside this crate, which talks over its stdin and stdout
+//! in a line-based protocol much like UCI for chess. Positions and moves are written as in [`super::notation`].
+//!
+//! ```text
+//! > cerke
+//! < id name Some Engine                              any number of `id` lines, or none
+//! < cerkeok
+//! > position turn IA season 0 rate 1 scores 20 20 …
+//! > go movetime 1000
+//! < bestmove inf LIA via LAU LY tactic free_lunch    `tactic …` is optional
+//! > go sticks 3 movetime 1000                        only after an `inf`: how many sticks landed face up
+//! < bestmove LY                                      or `bestmove cancel` to go back to where it was
+//! > position …                                       only if the move has completed a hand
+//! > go hand movetime 1000
+//! < bestmove tymok                                   or `bestmove taxot`
+//! > quit
+//! ```
+//!
+//! Any other line from the engine, such as `info …`, is ignored. An engine that does not answer in time,
+//! answers with a move that cannot be played, or exits, is stopped, and the rest of its turn is played by
+//! [`HeuristicBot`] instead, so that a broken engine never stalls a game. A new process is started for every turn.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use cetkaik_core::absolute::parse_coord;
+use cetkaik_full_state_transition::{
+    message::{AfterHalfAcceptance, InfAfterStep},
+    state::{ExcitedState, GroundState, HandNotResolved},
+    Config,
+};
+
+use super::bot::{Bot, BotMoveWithTactics};
+use super::heuristic::HeuristicBot;
+use super::notation::{parse_move, serialize_position, Position};
+use super::tymok::HandDecision;
+use crate::types::bot::TacticsKey;
+use crate::types::LockOrRecover;
+
+/// How long the engine has to start up and answer `cerke`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How late past `movetime` an answer is still waited for
+const GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+
+    /// How long the engine is asked to think about each decision
+    pub movetime: Duration,
+}
+
+impl EngineConfig {
+    /// Reads `BOT_ENGINE`, the path of the executable, and `BOT_ENGINE_ARGS` and `BOT_ENGINE_MOVETIME_MILLIS`.
+    /// `None` if no engine is configured.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let program = std::env::var_os("BOT_ENGINE")?;
+        let args = std::env::var("BOT_ENGINE_ARGS")
+            .map(|args| args.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+        let movetime = std::env::var("BOT_ENGINE_MOVETIME_MILLIS")
+            .ok()
+            .and_then(|millis| millis.parse().ok())
+            .map_or(Duration::from_secs(1), Duration::from_millis);
+        Some(Self {
+            program: program.into(),
+            args,
+            movetime,
+        })
+    }
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+
+    /// The engine's output, read line by line on a thread of its own so that waiting for it can time out
+    lines: Receiver<String>,
+}
+
+impl Process {
+    fn spawn(config: &EngineConfig) -> io::Result<Self> {
+        let mut child = Command::new(&config.program)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(io::Error::other(
+                "the engine's stdin and stdout are not piped",
+            ));
+        };
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()
+    }
+
+    /// The rest of the first line that starts with `keyword`, skipping every other line.
+    /// `None` if no such line comes within `timeout`, or if the engine has exited.
+    fn answer(&self, keyword: &str, timeout: Duration) -> Option<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            let line = self.lines.recv_timeout(remaining).ok()?;
+            let mut words = line.splitn(2, ' ');
+            if words.next() == Some(keyword) {
+                return Some(words.next().unwrap_or_default().trim().to_string());
+            }
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+enum EngineState {
+    NotStarted,
+    Running(Process),
+
+    /// The engine has misbehaved in this turn, and is not asked anything more
+    GaveUp,
+}
+
+/// Plays through the engine of `EngineConfig`, and as [`HeuristicBot`] wherever the engine fails
+pub struct EngineBot {
+    config: EngineConfig,
+    state: Mutex<EngineState>,
+
+    /// That of the position the turn started from, which the state after the sticks are cast no longer carries
+    tam_has_moved_previously: AtomicBool,
+}
+
+impl EngineBot {
+    #[must_use]
+    pub fn new(config: EngineConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(EngineState::NotStarted),
+            tam_has_moved_previously: AtomicBool::new(false),
+        }
+    }
+
+    fn start(&self) -> Option<Process> {
+        let mut process = Process::spawn(&self.config).ok()?;
+        process.send("cerke").ok()?;
+        process.answer("cerkeok", HANDSHAKE_TIMEOUT)?;
+        Some(process)
+    }
+
+    /// Sends `position` and `go`, which is completed with `movetime`, and returns what follows `bestmove` in the answer.
+    /// `None` once the engine has failed in any way.
+    fn ask(&self, position: &Position, go: &str) -> Option<String> {
+        let mut state = self.state.lock_or_recover();
+        if matches!(*state, EngineState::NotStarted) {
+            match self.start() {
+                Some(process) => *state = EngineState::Running(process),
+                None => self.give_up_on(&mut state, "did not start"),
+            }
+        }
+        let EngineState::Running(process) = &mut *state else {
+            return None;
+        };
+        let movetime = self.config.movetime;
+        let answer = process
+            .send(&format!("position {}", serialize_position(position)))
+            .and_then(|()| process.send(&format!("{go} movetime {}", movetime.as_millis())))
+            .ok()
+            .and_then(|()| process.answer("bestmove", movetime + GRACE));
+        if answer.is_none() {
+            self.give_up_on(&mut state, "did not answer in time");
+        }
+        answer
+    }
+
+    fn give_up(&self, why: &str) {
+        self.give_up_on(&mut self.state.lock_or_recover(), why);
+    }
+
+    /// Asks the engine nothing more in this turn, saying why only the first time
+    fn give_up_on(&self, state: &mut EngineState, why: &str) {
+        if !matches!(state, EngineState::GaveUp) {
+            eprintln!(
+                "the engine {} {why}; the rest of its turn is played by the heuristic bot",
+                self.config.program.display()
+            );
+            *state = EngineState::GaveUp;
+        }
+    }
+}
+
+impl Bot for EngineBot {
+    fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+        self.tam_has_moved_previously
+            .store(state.tam_has_moved_previously, Ordering::Relaxed);
+        let answer = self.ask(&Position::from(state), "go");
+        let chosen = answer.and_then(|answer| {
+            let (mov, tactics) = match answer.split_once(" tactic ") {
+                Some((mov, tactics)) => (mov, TacticsKey::try_from(tactics.trim()).ok()),
+                None => (answer.as_str(), None),
+            };
+            let mov = parse_move(mov)?;
+            let (hop1zuo1_candidates, candidates) = state.get_candidates(config);
+            (hop1zuo1_candidates.contains(&mov) || candidates.contains(&mov))
+                .then_some(BotMoveWithTactics { tactics, mov })
+        });
+        if chosen.is_none() {
+            self.give_up("gave no legal move");
+            return HeuristicBot.choose_move(state, config);
+        }
+        chosen
+    }
+
+    fn choose_after_half_acceptance(
+        &self,
+        planned: InfAfterStep,
+        state: &ExcitedState,
+        config: Config,
+    ) -> AfterHalfAcceptance {
+        let position = Position {
+            f: &state.c.f,
+            whose_turn: state.c.whose_turn,
+            season: state.c.season,
+            scores: state.c.scores,
+            rate: state.c.rate,
+            tam_has_moved_previously: self.tam_has_moved_previously.load(Ordering::Relaxed),
+        };
+        let chosen = self
+            .ask(&position, &format!("go sticks {}", state.ciurl))
+            .and_then(|answer| match answer.as_str() {
+                "cancel" => Some(AfterHalfAcceptance { dest: None }),
+                dest => parse_coord(dest).map(|dest| AfterHalfAcceptance { dest: Some(dest) }),
+            })
+            .filter(|after| state.get_candidates(config).contains(after));
+        chosen.unwrap_or_else(|| {
+            self.give_up("gave no legal destination");
+            HeuristicBot.choose_after_half_acceptance(planned, state, config)
+        })
+    }
+
+    fn choose_hand_decision(&self, state: &HandNotResolved, config: Config) -> HandDecision {
+        let position = Position {
+            f: &state.f,
+            whose_turn: state.whose_turn,
+            season: state.season,
+            scores: state.scores,
+            rate: state.rate,
+            // the position after the move, in which it is the Tam2 just moved that counts
+            tam_has_moved_previously: state.i_have_moved_tam_in_this_turn,
+        };
+        match self.ask(&position, "go hand").as_deref() {
+            Some("tymok") => HandDecision::TyMok,
+            Some("taxot") => HandDecision::TaXot,
+            _ => {
+                self.give_up("gave neither tymok nor taxot");
+                HeuristicBot.choose_hand_decision(state, config)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use cetkaik_full_state_transition::{
+        apply_inf_after_step,
+        message::{AfterHalfAcceptance, PureMove},
+        Config,
+    };
+
+    use super::{EngineBot, EngineConfig, EngineState};
+    use crate::bot::bot::Bot;
+    use crate::bot::notation::serialize_move;
+    use crate::types::bot::TacticsKey;
+
+    fn sh(script: String) -> EngineBot {
+        EngineBot::new(EngineConfig {
+            program: PathBuf::from("sh"),
+            args: vec!["-c".to_string(), script],
+            movetime: Duration::from_millis(100),
+        })
+    }
+
+    #[test]
+    fn plays_the_engine_s_move_or_falls_back() {
+        let config = Config::cerke_online_alpha();
+        let (state, _) = cetkaik_full_state_transition::initial_state().choose();
+        let (_, candidates) = state.get_candidates(config);
+        let mov = candidates.last().unwrap().clone();
+
+        let engine = sh(format!(
+            r#"while read line; do case "$line" in
+                cerke) echo "id name test"; echo cerkeok;;
+                "go movetime"*) echo "info thinking"; echo "bestmove {} tactic free_lunch";;
+                quit) exit;;
+            esac; done"#,
+            serialize_move(&mov)
+        ));
+        let chosen = engine.choose_move(&state, config).unwrap();
+        assert_eq!(mov, chosen.mov);
+        assert_eq!(Some(TacticsKey::FreeLunch), chosen.tactics);
+
+        // an engine that never answers is given up on, and the move is still made
+        let silent = sh("read line; echo cerkeok; sleep 10".to_string());
+        let started = Instant::now();
+        let chosen = silent.choose_move(&state, config).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(candidates.contains(&chosen.mov));
+    }
+
+    #[test]
+    fn the_position_after_the_sticks_keeps_the_tam2_flag() {
+        let config = Config::cerke_online_alpha();
+        let (mut state, _) = cetkaik_full_state_transition::initial_state().choose();
+        state.tam_has_moved_previously = true;
+        let (_, candidates) = state.get_candidates(config);
+        let Some(PureMove::InfAfterStep(planned)) = candidates
+            .into_iter()
+            .find(|mov| matches!(mov, PureMove::InfAfterStep(_)))
+        else {
+            panic!("the initial position has a move stepping over a piece");
+        };
+
+        // cancels only if told that Tam2 has moved, and otherwise answers what cannot be played
+        let engine = sh(format!(
+            r#"while read line; do case "$line" in
+                cerke) echo cerkeok;;
+                position*"tam_moved true"*) tam=true;;
+                position*) tam=false;;
+                "go movetime"*) echo "bestmove {}";;
+                "go sticks"*) if [ "$tam" = true ]; then echo "bestmove cancel"; else echo "bestmove nowhere"; fi;;
+                quit) exit;;
+            esac; done"#,
+            serialize_move(&PureMove::InfAfterStep(planned))
+        ));
+        assert_eq!(
+            PureMove::InfAfterStep(planned),
+            engine.choose_move(&state, config).unwrap().mov
+        );
+        let (excited, _) = apply_inf_after_step(&state, planned, config)
+            .unwrap()
+            .choose();
+        assert_eq!(
+            AfterHalfAcceptance { dest: None },
+            engine.choose_after_half_acceptance(planned, &excited, config)
+        );
+        assert!(matches!(
+            *engine.state.lock().unwrap(),
+            EngineState::Running(_)
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+//! Positions for the bots' tests
+
+use cetkaik_core::absolute::{Coord, Field, Piece, Side};
+use cetkaik_core::{Color, Profession};
+use cetkaik_full_state_transition::{state::GroundState, Rate, Scores, Season};
+
+/// A non-Tam piece of `color` and `prof`, belonging to `side`
+pub fn piece(color: Color, prof: Profession, side: Side) -> Piece {
+    Piece::NonTam2Piece { color, prof, side }
+}
+
+/// A position in the first season with only `pieces` on the board, no piece in either hand, and the IA side to move
+pub fn board_from_pieces(
+    pieces: impl IntoIterator<Item = (Coord, Piece)>,
+    scores: Scores,
+) -> GroundState {
+    GroundState {
+        f: Field {
+            board: pieces.into_iter().collect(),
+            a_side_hop1zuo1: vec![],
+            ia_side_hop1zuo1: vec![],
+        },
+        whose_turn: Side::IASide,
+        season: Season::Iei2,
+        scores,
+        rate: Rate::X1,
+        tam_has_moved_previously: false,
+    }
+}
@@ -0,0 +1,633 @@
+//! A bot that looks one move ahead. Every candidate is played out over each way the sticks can fall,
+//! and each outcome is scored by what it captures, whether it completes a hand, what it costs in
+//! penalties around the Tam2, and which of the bot's pieces it leaves for the opponent to take.
+//! The move is then labelled with the [`TacticsKey`] that best explains why it was chosen.
+
+use cetkaik_calculate_hand::calculate_hands_and_score_from_pieces;
+use cetkaik_core::absolute::{self, Coord, NonTam2Piece, Piece, Side};
+use cetkaik_core::Profession;
+use cetkaik_full_state_transition::{
+    apply_after_half_acceptance, apply_inf_after_step, apply_normal_move,
+    message::{NormalMove, PureMove},
+    probabilistic::{Prob, Probabilistic},
+    resolve,
+    state::{GroundState, HandNotResolved, HandResolved},
+    Config, IfTaxot, Rate,
+};
+use rand::prelude::SliceRandom;
+
+use super::bot::{Bot, BotMove, BotMoveWithTactics};
+use super::tymok::{score_of, victory_of};
+use crate::types::bot::TacticsKey;
+
+/// What winning or losing the game outright is worth, far beyond anything else
+const GAME: f64 = 1000.0;
+
+/// How much a point of score is worth, on the scale of [`piece_value`]
+pub(crate) const POINT: f64 = 2.0;
+
+/// The opponent does not always see a capture, nor always take it
+const DANGER_DISCOUNT: f64 = 0.8;
+
+/// A Tuk2 in the water moves more freely
+const SHAMAN_IN_WATER: f64 = 0.5;
+
+/// How many candidates, at most, are played out and examined against the opponent's replies.
+/// Beyond that, the rest are the least promising ones, and playing them out costs more than it gains.
+const MAX_EXAMINED: usize = 24;
+
+pub(crate) fn piece_value(prof: Profession) -> f64 {
+    match prof {
+        Profession::Kauk2 => 1.0,
+        Profession::Gua2
+        | Profession::Kaun1
+        | Profession::Dau2
+        | Profession::Maun1
+        | Profession::Kua2 => 2.0,
+        Profession::Nuak1 | Profession::Tuk2 | Profession::Uai1 => 3.0,
+        Profession::Io => 5.0,
+    }
+}
+
+pub(crate) fn hand_score(pieces: &[NonTam2Piece]) -> i32 {
+    calculate_hands_and_score_from_pieces(pieces).map_or(0, |answer| answer.score)
+}
+
+/// The worst capture that the opponent, to move in `state`, can make against `me`
+#[derive(Debug, Default, Clone, Copy)]
+struct Threat {
+    value: f64,
+    completes_hand: bool,
+
+    /// The probability that a capture completes a hand worth enough to end the game
+    wins: f64,
+}
+
+/// The probability that at least `n` of the five sticks land face up
+fn sticks_at_least(n: i32) -> f64 {
+    const WAYS: [f64; 6] = [1.0, 5.0, 10.0, 10.0, 5.0, 1.0];
+    (0..)
+        .zip(WAYS)
+        .filter(|(count, _)| *count >= n)
+        .map(|(_, ways)| ways / 32.0)
+        .sum()
+}
+
+/// The square where `mov` would capture, if anywhere, and the probability that the piece gets there
+fn reach(state: &GroundState, mov: &PureMove) -> Option<(Coord, f64)> {
+    let (src, dest, likelihood) = match mov {
+        PureMove::NormalMove(
+            NormalMove::NonTamMoveSrcDst { src, dest }
+            | NormalMove::NonTamMoveSrcStepDstFinite { src, dest, .. },
+        ) => (*src, *dest, 1.0),
+        PureMove::InfAfterStep(m) => (
+            m.src,
+            m.planned_direction,
+            sticks_at_least(absolute::distance(m.step, m.planned_direction)),
+        ),
+        PureMove::NormalMove(_) => return None,
+    };
+    let enters_water = !absolute::is_water(src)
+        && absolute::is_water(dest)
+        && !state
+            .f
+            .board
+            .get(&src)
+            .is_some_and(|piece| piece.has_prof(Profession::Nuak1));
+    Some((
+        dest,
+        if enters_water {
+            likelihood * sticks_at_least(3)
+        } else {
+            likelihood
+        },
+    ))
+}
+
+/// What taking `piece` is worth to the side whose hop1zuo1 is `hop1zuo1`, and whether it completes a hand
+fn worth_taking(hop1zuo1: &[NonTam2Piece], piece: NonTam2Piece, rate: Rate) -> (f64, bool) {
+    let mut with_it = hop1zuo1.to_vec();
+    with_it.push(piece);
+    let hand_gain = hand_score(&with_it) - hand_score(hop1zuo1);
+    (
+        piece_value(piece.prof) + f64::from(hand_gain * rate.num()) * POINT,
+        hand_gain > 0,
+    )
+}
+
+/// The piece of `side` on `coord`, if any
+fn piece_of(state: &GroundState, side: Side, coord: Coord) -> Option<NonTam2Piece> {
+    match state.f.board.get(&coord) {
+        Some(Piece::NonTam2Piece {
+            color,
+            prof,
+            side: owner,
+        }) if *owner == side => Some(NonTam2Piece {
+            color: *color,
+            prof: *prof,
+        }),
+        _ => None,
+    }
+}
+
+pub(crate) fn hop1zuo1_of(state: &GroundState, side: Side) -> &[NonTam2Piece] {
+    match side {
+        Side::IASide => &state.f.ia_side_hop1zuo1,
+        Side::ASide => &state.f.a_side_hop1zuo1,
+    }
+}
+
+fn threat(state: &GroundState, me: Side, config: Config) -> Threat {
+    let (_, candidates) = state.get_candidates(config);
+    let mut worst = Threat::default();
+    for mov in &candidates {
+        let Some((dest, likelihood)) = reach(state, mov) else {
+            continue;
+        };
+        let Some(piece) = piece_of(state, me, dest) else {
+            continue;
+        };
+        let hop1zuo1 = hop1zuo1_of(state, !me);
+        let (value, completes_hand) = worth_taking(hop1zuo1, piece, state.rate);
+        if completes_hand {
+            let mut with_it = hop1zuo1.to_vec();
+            with_it.push(piece);
+            if hand_score(&with_it) * state.rate.num() >= score_of(me, state.scores) {
+                worst.wins = worst.wins.max(likelihood);
+            }
+        }
+        if likelihood * value > worst.value {
+            worst = Threat {
+                value: likelihood * value,
+                completes_hand,
+                ..worst
+            };
+        }
+    }
+    worst
+}
+
+/// The expectation, over the casts of the sticks, of how good a move is for the side making it
+#[derive(Debug, Default, Clone, Copy)]
+struct Evaluation {
+    value: f64,
+    win: f64,
+    loss: f64,
+    captured: f64,
+    threat: f64,
+
+    /// The probability that the opponent is left a capture that completes a hand
+    hand_threat: f64,
+
+    strengthened_shaman: bool,
+}
+
+impl Evaluation {
+    fn add(&mut self, other: Evaluation, prob: f64) {
+        self.value += prob * other.value;
+        self.win += prob * other.win;
+        self.loss += prob * other.loss;
+        self.captured += prob * other.captured;
+        self.threat += prob * other.threat;
+        self.hand_threat += prob * other.hand_threat;
+    }
+}
+
+fn evaluate_outcome(before: &GroundState, after: &HandNotResolved, config: Config) -> Evaluation {
+    let me = before.whose_turn;
+    let (hop1zuo1_before, hop1zuo1_after) = match me {
+        Side::IASide => (&before.f.ia_side_hop1zuo1, &after.f.ia_side_hop1zuo1),
+        Side::ASide => (&before.f.a_side_hop1zuo1, &after.f.a_side_hop1zuo1),
+    };
+    let captured = if hop1zuo1_after.len() > hop1zuo1_before.len() {
+        hop1zuo1_after
+            .last()
+            .map_or(0.0, |piece| piece_value(piece.prof))
+    } else {
+        0.0
+    };
+    let my_score = score_of(me, before.scores);
+    let win = Evaluation {
+        value: GAME,
+        win: 1.0,
+        captured,
+        ..Evaluation::default()
+    };
+
+    match resolve(after, config) {
+        HandResolved::NeitherTymokNorTaxot(next) => {
+            let threat = threat(&next, me, config);
+            Evaluation {
+                value: captured + f64::from(score_of(me, next.scores) - my_score) * POINT
+                    - DANGER_DISCOUNT * threat.value
+                    - GAME * threat.wins,
+                loss: threat.wins,
+                captured,
+                threat: threat.value,
+                hand_threat: if threat.completes_hand { 1.0 } else { 0.0 },
+                ..Evaluation::default()
+            }
+        }
+        HandResolved::HandExists { if_taxot, .. } => match if_taxot {
+            IfTaxot::VictoriousSide(victor) if Some(victor) == victory_of(me) => win,
+            IfTaxot::NextSeason(next) => {
+                // a hand that would lose points is followed by a ty mok, and costs nothing for now
+                let gain = (score_of(me, next.choose().0.scores) - my_score).max(0);
+                Evaluation {
+                    value: captured + f64::from(gain) * POINT,
+                    captured,
+                    ..Evaluation::default()
+                }
+            }
+            // a ta xot would lose the game, so the bot will ty mok and play on
+            IfTaxot::VictoriousSide(_) => Evaluation {
+                value: captured,
+                captured,
+                ..Evaluation::default()
+            },
+        },
+        HandResolved::GameEndsWithoutTymokTaxot(victor) => {
+            if Some(victor) == victory_of(me) {
+                win
+            } else {
+                Evaluation {
+                    value: -GAME,
+                    loss: 1.0,
+                    captured,
+                    ..Evaluation::default()
+                }
+            }
+        }
+    }
+}
+
+/// `Prob` only lends out its entries, hence the clones
+fn flatten<T: Clone>(p: Probabilistic<T>) -> Vec<(T, Option<usize>, f64)> {
+    Prob::from(p)
+        .0
+        .iter()
+        .map(|((t, ciurl), prob)| (t.clone(), *ciurl, *prob))
+        .collect()
+}
+
+/// Every state that `bot_move` can lead to once played by [`play_turn`](super::play_turn), with its probability
+fn outcomes(
+    state: &GroundState,
+    bot_move: &BotMove,
+    config: Config,
+) -> Vec<(HandNotResolved, f64)> {
+    match bot_move {
+        BotMove::NormalMove(mov) => apply_normal_move(state, *mov, config)
+            .map(flatten)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(state, _, p)| (state, p))
+            .collect(),
+        BotMove::InfAfterStep { dat, after } => {
+            let Ok(sticks) = apply_inf_after_step(state, *dat, config) else {
+                return vec![];
+            };
+            flatten(sticks)
+                .into_iter()
+                .flat_map(|(excited, ciurl, p)| {
+                    apply_after_half_acceptance(&excited, after[ciurl.unwrap_or(0)], config)
+                        .map(flatten)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(move |(state, _, q)| (state, p * q))
+                })
+                .collect()
+        }
+    }
+}
+
+fn strengthens_shaman(state: &GroundState, mov: &PureMove) -> bool {
+    match mov {
+        PureMove::NormalMove(
+            NormalMove::NonTamMoveSrcDst { src, dest }
+            | NormalMove::NonTamMoveSrcStepDstFinite { src, dest, .. },
+        ) => {
+            !absolute::is_water(*src)
+                && absolute::is_water(*dest)
+                && state
+                    .f
+                    .board
+                    .get(src)
+                    .is_some_and(|piece| piece.has_prof(Profession::Tuk2))
+        }
+        PureMove::NormalMove(NormalMove::NonTamMoveFromHopZuo { prof, dest, .. }) => {
+            *prof == Profession::Tuk2 && absolute::is_water(*dest)
+        }
+        PureMove::NormalMove(_) | PureMove::InfAfterStep(_) => false,
+    }
+}
+
+fn evaluate(state: &GroundState, mov: &PureMove, config: Config) -> Option<Evaluation> {
+    let outcomes = outcomes(state, &BotMove::from_strict_pure_move(mov), config);
+    if outcomes.is_empty() {
+        return None;
+    }
+    let mut evaluation = Evaluation {
+        strengthened_shaman: strengthens_shaman(state, mov),
+        ..Evaluation::default()
+    };
+    for (after, prob) in &outcomes {
+        evaluation.add(evaluate_outcome(state, after, config), *prob);
+    }
+    if evaluation.strengthened_shaman {
+        evaluation.value += SHAMAN_IN_WATER;
+    }
+    Some(evaluation)
+}
+
+/// What the board alone tells about `mov`, without playing it out: the capture it may make, and the Tuk2 bonus.
+/// Playing a move out is what costs, so this decides which candidates are worth playing out.
+pub(crate) fn estimate(state: &GroundState, mov: &PureMove) -> f64 {
+    let me = state.whose_turn;
+    let capture = reach(state, mov).and_then(|(dest, likelihood)| {
+        let piece = piece_of(state, !me, dest)?;
+        Some(likelihood * worth_taking(hop1zuo1_of(state, me), piece, state.rate).0)
+    });
+    let shaman = if strengthens_shaman(state, mov) {
+        SHAMAN_IN_WATER
+    } else {
+        0.0
+    };
+    capture.unwrap_or(0.0) + shaman
+}
+
+/// What the opponent threatened before the side to move in `game_state` made its move
+fn threat_before(game_state: &GroundState, config: Config) -> Threat {
+    threat(
+        &GroundState {
+            whose_turn: !game_state.whose_turn,
+            ..game_state.clone()
+        },
+        game_state.whose_turn,
+        config,
+    )
+}
+
+fn tactics(chosen: &Evaluation, threat_before: Threat) -> TacticsKey {
+    if chosen.win >= 0.5 {
+        TacticsKey::VictoryAlmostCertain
+    } else if chosen.loss >= 0.5 {
+        TacticsKey::LossAlmostCertain
+    } else if threat_before.completes_hand && chosen.hand_threat < 0.5 {
+        TacticsKey::AvoidDefeat
+    } else if chosen.captured > 0.0 && chosen.threat <= 0.0 {
+        TacticsKey::FreeLunch
+    } else if chosen.strengthened_shaman {
+        TacticsKey::StrengthenedShaman
+    } else {
+        TacticsKey::Neutral
+    }
+}
+
+/// Plays the candidate with the best expected outcome, breaking ties at random.
+/// `None` if there is no move to make at all.
+#[must_use]
+pub fn bot_heuristic(game_state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+    let (hop1zuo1_candidates, candidates) = game_state.get_candidates(config);
+    let mut candidates: Vec<_> = hop1zuo1_candidates.into_iter().chain(candidates).collect();
+    candidates.shuffle(&mut rand::thread_rng());
+
+    // Since the opponent's replies only lower the value, the estimate is seldom beaten;
+    // the candidates are played out from the most promising, until none of the rest looks better than the best so far.
+    let mut estimated: Vec<_> = candidates
+        .iter()
+        .map(|mov| (mov, estimate(game_state, mov)))
+        .collect();
+    estimated.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut best: Option<(&PureMove, Evaluation)> = None;
+    for (mov, estimate) in estimated.into_iter().take(MAX_EXAMINED) {
+        if best
+            .as_ref()
+            .is_some_and(|(_, best)| best.value >= estimate)
+        {
+            break;
+        }
+        if let Some(next) = evaluate(game_state, mov, config) {
+            if best
+                .as_ref()
+                .is_none_or(|(_, best)| next.value > best.value)
+            {
+                best = Some((mov, next));
+            }
+        }
+    }
+    let (mov, evaluation) = best?;
+
+    Some(BotMoveWithTactics {
+        tactics: Some(tactics(&evaluation, threat_before(game_state, config))),
+        mov: mov.clone(),
+    })
+}
+
+/// The [`TacticsKey`] that [`bot_heuristic`] would have given `mov`, for a move chosen some other way
+pub(crate) fn explain(game_state: &GroundState, mov: &PureMove, config: Config) -> TacticsKey {
+    evaluate(game_state, mov, config).map_or(TacticsKey::Neutral, |evaluation| {
+        tactics(&evaluation, threat_before(game_state, config))
+    })
+}
+
+/// Plays as [`bot_heuristic`] does
+pub struct HeuristicBot;
+
+impl Bot for HeuristicBot {
+    fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+        bot_heuristic(state, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cetkaik_core::absolute::{self, Column, Coord, Piece, Row, Side};
+    use cetkaik_core::{Color, Profession};
+    use cetkaik_full_state_transition::{
+        message::{NormalMove, PureMove},
+        state::GroundState,
+        Config, Rate, Scores,
+    };
+
+    use super::bot_heuristic;
+    use crate::bot::fixtures::{board_from_pieces, piece};
+    use crate::types::bot::TacticsKey;
+
+    /// Where `mov` takes a piece from and to, if it moves a piece on the board without stepping
+    fn src_dest(mov: &PureMove) -> Option<(Coord, Coord)> {
+        match mov {
+            PureMove::NormalMove(NormalMove::NonTamMoveSrcDst { src, dest }) => Some((*src, *dest)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn takes_a_free_lunch() {
+        let io = Coord(Row::A, Column::K);
+        let kauk2 = Coord(Row::E, Column::K);
+        let state = board_from_pieces(
+            [
+                (io, piece(Color::Kok1, Profession::Io, Side::IASide)),
+                (kauk2, piece(Color::Huok2, Profession::Kauk2, Side::ASide)),
+                (Coord(Row::O, Column::Z), Piece::Tam2),
+            ],
+            Scores::new(),
+        );
+
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::FreeLunch), chosen.tactics);
+        assert!(matches!(
+            chosen.mov,
+            PureMove::NormalMove(NormalMove::NonTamMoveSrcDst { src, dest }) if src == io && dest == kauk2
+        ));
+    }
+
+    #[test]
+    fn wins_when_it_can() {
+        let io = Coord(Row::E, Column::K);
+        let their_io = Coord(Row::A, Column::K);
+        let state = board_from_pieces(
+            [
+                (io, piece(Color::Kok1, Profession::Io, Side::IASide)),
+                (their_io, piece(Color::Huok2, Profession::Io, Side::ASide)),
+                (Coord(Row::O, Column::Z), Piece::Tam2),
+            ],
+            Scores::new().edit(16, Side::IASide, Rate::X1).unwrap(),
+        );
+
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::VictoryAlmostCertain), chosen.tactics);
+        assert_eq!(Some((io, their_io)), src_dest(&chosen.mov));
+    }
+
+    #[test]
+    fn saves_its_io_from_a_hand() {
+        let io = Coord(Row::AU, Column::L);
+        // with the Tam2 held still, there are few enough moves for the bot to examine every one
+        let state = GroundState {
+            tam_has_moved_previously: true,
+            ..board_from_pieces(
+                [
+                    (io, piece(Color::Kok1, Profession::Io, Side::IASide)),
+                    (
+                        Coord(Row::A, Column::L),
+                        piece(Color::Huok2, Profession::Gua2, Side::ASide),
+                    ),
+                    (Coord(Row::A, Column::P), Piece::Tam2),
+                ],
+                Scores::new(),
+            )
+        };
+
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::AvoidDefeat), chosen.tactics);
+        let (src, dest) = src_dest(&chosen.mov).unwrap();
+        assert_eq!(io, src);
+        assert_ne!(Column::L, dest.1);
+    }
+
+    #[test]
+    fn moves_a_threatened_piece_out_of_danger() {
+        let uai1 = Coord(Row::AU, Column::L);
+        let state = GroundState {
+            tam_has_moved_previously: true,
+            ..board_from_pieces(
+                [
+                    (uai1, piece(Color::Kok1, Profession::Uai1, Side::IASide)),
+                    (
+                        Coord(Row::Y, Column::P),
+                        piece(Color::Kok1, Profession::Kauk2, Side::IASide),
+                    ),
+                    (
+                        Coord(Row::A, Column::L),
+                        piece(Color::Huok2, Profession::Gua2, Side::ASide),
+                    ),
+                    (Coord(Row::A, Column::P), Piece::Tam2),
+                ],
+                Scores::new(),
+            )
+        };
+
+        // losing a Uai1 completes no hand, so this is no more than a piece kept
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::Neutral), chosen.tactics);
+        let (src, dest) = src_dest(&chosen.mov).unwrap();
+        assert_eq!(uai1, src);
+        assert_ne!(Column::L, dest.1);
+    }
+
+    #[test]
+    fn sends_its_tuk2_into_the_water() {
+        let tuk2 = Coord(Row::Y, Column::N);
+        let state = board_from_pieces(
+            [
+                (tuk2, piece(Color::Kok1, Profession::Tuk2, Side::IASide)),
+                (Coord(Row::A, Column::P), Piece::Tam2),
+            ],
+            Scores::new(),
+        );
+
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::StrengthenedShaman), chosen.tactics);
+        let (src, dest) = src_dest(&chosen.mov).unwrap();
+        assert_eq!(tuk2, src);
+        assert!(absolute::is_water(dest));
+    }
+
+    #[test]
+    fn counts_the_reach_the_tam2_gives() {
+        // next to the Tam2, a Gua2 moves diagonally instead
+        let uai1 = Coord(Row::AI, Column::M);
+        let state = GroundState {
+            tam_has_moved_previously: true,
+            ..board_from_pieces(
+                [
+                    (uai1, piece(Color::Kok1, Profession::Uai1, Side::IASide)),
+                    (
+                        Coord(Row::A, Column::L),
+                        piece(Color::Huok2, Profession::Gua2, Side::ASide),
+                    ),
+                    (Coord(Row::A, Column::K), Piece::Tam2),
+                ],
+                Scores::new(),
+            )
+        };
+
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::Neutral), chosen.tactics);
+        let (src, dest) = src_dest(&chosen.mov).unwrap();
+        assert_eq!(uai1, src);
+        assert_ne!(Coord(Row::Y, Column::C), dest);
+        assert_ne!(Coord(Row::AU, Column::P), dest);
+    }
+
+    #[test]
+    fn knows_when_every_move_loses() {
+        let io = Coord(Row::IA, Column::K);
+        let state = GroundState {
+            tam_has_moved_previously: true,
+            ..board_from_pieces(
+                [
+                    (io, piece(Color::Kok1, Profession::Io, Side::IASide)),
+                    (
+                        Coord(Row::A, Column::K),
+                        piece(Color::Huok2, Profession::Gua2, Side::ASide),
+                    ),
+                    (
+                        Coord(Row::A, Column::L),
+                        piece(Color::Kok1, Profession::Gua2, Side::ASide),
+                    ),
+                    (Coord(Row::O, Column::Z), Piece::Tam2),
+                ],
+                Scores::new().edit(-17, Side::IASide, Rate::X1).unwrap(),
+            )
+        };
+
+        // wherever the Io goes, one of the Gua2 takes it and the game with it
+        let chosen = bot_heuristic(&state, Config::cerke_online_alpha()).unwrap();
+        assert_eq!(Some(TacticsKey::LossAlmostCertain), chosen.tactics);
+        assert_eq!(Some(io), src_dest(&chosen.mov).map(|(src, _)| src));
+    }
+}
@@ -1,4 +1,12 @@
-
+#[allow(clippy::module_inception)]
 pub mod bot;
+pub mod engine;
+#[cfg(test)]
+mod fixtures;
+pub mod heuristic;
+pub mod notation;
+pub mod search;
+pub mod thinking;
+pub mod tymok;
 
-pub use bot::bot_move;
\ No newline at end of file
+pub use bot::{bot_for, bot_move, play_turn, Bot, BotSettings, ThinkTime};
@@ -0,0 +1,257 @@
+//! Positions and moves as text, for bots that run outside this crate (see [`super::engine`]).
+//!
+//! Squares are written as in `cetkaik_core` (`LIA`, `ZO`, …) and pieces by colour and profession (`赤王`, `黒兵`);
+//! a Tam2 is `皇`. A move is one of
+//!
+//! ```text
+//! move LIA LAU            a piece moves without stepping
+//! move LIA via LAU LAI    a piece steps over LAU
+//! inf LIA via LAU LY      a piece steps over LAU and casts the sticks to go as far as it can towards LY
+//! drop 赤兵 LAI            a piece comes back from the hop1zuo1
+//! tam ZO ZU ZY            the Tam2 moves twice
+//! tam ZO via ZU ZY ZAI    the Tam2 steps during its first move
+//! tam ZO ZU via ZY ZAI    the Tam2 steps during its second move
+//! ```
+
+use cetkaik_core::absolute::{parse_coord, serialize_coord, Coord, NonTam2Piece, Piece, Side};
+use cetkaik_core::{serialize_color, serialize_prof};
+use cetkaik_full_state_transition::{
+    message::{InfAfterStep, NormalMove, PureMove},
+    state::GroundState,
+    Rate, Scores, Season,
+};
+
+fn serialize_side(side: Side) -> &'static str {
+    match side {
+        Side::IASide => "IA",
+        Side::ASide => "A",
+    }
+}
+
+fn serialize_piece(piece: NonTam2Piece) -> String {
+    format!(
+        "{}{}",
+        serialize_color(piece.color),
+        serialize_prof(piece.prof)
+    )
+}
+
+fn serialize_pieces(pieces: &[NonTam2Piece]) -> String {
+    if pieces.is_empty() {
+        return "-".to_string();
+    }
+    pieces
+        .iter()
+        .map(|piece| serialize_piece(*piece))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// What a position carries beyond the [`GroundState`], which is not always at hand
+pub struct Position<'a> {
+    pub f: &'a cetkaik_core::absolute::Field,
+    pub whose_turn: Side,
+    pub season: Season,
+    pub scores: Scores,
+    pub rate: Rate,
+    pub tam_has_moved_previously: bool,
+}
+
+impl<'a> From<&'a GroundState> for Position<'a> {
+    fn from(state: &'a GroundState) -> Self {
+        Position {
+            f: &state.f,
+            whose_turn: state.whose_turn,
+            season: state.season,
+            scores: state.scores,
+            rate: state.rate,
+            tam_has_moved_previously: state.tam_has_moved_previously,
+        }
+    }
+}
+
+/// A single line, without the `position` keyword:
+///
+/// ```text
+/// turn IA season 0 rate 1 scores 20 20 tam_moved false board ZO:皇,KA:黒王:A,… hop1zuo1 - 赤兵,黒弓
+/// ```
+///
+/// where the scores and the hop1zuo1 are the IA side's then the A side's, and the board lists the occupied squares, in no particular order.
+#[must_use]
+pub fn serialize_position(position: &Position) -> String {
+    let mut board: Vec<_> = position
+        .f
+        .board
+        .iter()
+        .map(|(coord, piece)| match piece {
+            Piece::Tam2 => format!("{}:皇", serialize_coord(*coord)),
+            Piece::NonTam2Piece { color, prof, side } => format!(
+                "{}:{}:{}",
+                serialize_coord(*coord),
+                serialize_piece(NonTam2Piece {
+                    color: *color,
+                    prof: *prof
+                }),
+                serialize_side(*side)
+            ),
+        })
+        .collect();
+    // the board is a `HashMap`; sorting keeps the line the same for the same position
+    board.sort();
+    format!(
+        "turn {} season {} rate {} scores {} {} tam_moved {} board {} hop1zuo1 {} {}",
+        serialize_side(position.whose_turn),
+        position.season.to_index(),
+        position.rate.num(),
+        position.scores.ia(),
+        position.scores.a(),
+        position.tam_has_moved_previously,
+        if board.is_empty() {
+            "-".to_string()
+        } else {
+            board.join(",")
+        },
+        serialize_pieces(&position.f.ia_side_hop1zuo1),
+        serialize_pieces(&position.f.a_side_hop1zuo1),
+    )
+}
+
+#[must_use]
+pub fn serialize_move(mov: &PureMove) -> String {
+    let c = |coord: &Coord| serialize_coord(*coord);
+    match mov {
+        PureMove::NormalMove(NormalMove::NonTamMoveSrcDst { src, dest }) => {
+            format!("move {} {}", c(src), c(dest))
+        }
+        PureMove::NormalMove(NormalMove::NonTamMoveSrcStepDstFinite { src, step, dest }) => {
+            format!("move {} via {} {}", c(src), c(step), c(dest))
+        }
+        PureMove::InfAfterStep(InfAfterStep {
+            src,
+            step,
+            planned_direction,
+        }) => format!("inf {} via {} {}", c(src), c(step), c(planned_direction)),
+        PureMove::NormalMove(NormalMove::NonTamMoveFromHopZuo { color, prof, dest }) => format!(
+            "drop {} {}",
+            serialize_piece(NonTam2Piece {
+                color: *color,
+                prof: *prof
+            }),
+            c(dest)
+        ),
+        PureMove::NormalMove(NormalMove::TamMoveNoStep {
+            src,
+            first_dest,
+            second_dest,
+        }) => format!("tam {} {} {}", c(src), c(first_dest), c(second_dest)),
+        PureMove::NormalMove(NormalMove::TamMoveStepsDuringFormer {
+            src,
+            step,
+            first_dest,
+            second_dest,
+        }) => format!(
+            "tam {} via {} {} {}",
+            c(src),
+            c(step),
+            c(first_dest),
+            c(second_dest)
+        ),
+        PureMove::NormalMove(NormalMove::TamMoveStepsDuringLatter {
+            src,
+            step,
+            first_dest,
+            second_dest,
+        }) => format!(
+            "tam {} {} via {} {}",
+            c(src),
+            c(first_dest),
+            c(step),
+            c(second_dest)
+        ),
+    }
+}
+
+/// Parses what [`serialize_move`] writes. `None` if `s` is not a move, whether or not it is legal anywhere.
+#[must_use]
+pub fn parse_move(s: &str) -> Option<PureMove> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let c = |i: usize| parse_coord(words.get(i)?);
+    let via = |i: usize| words.get(i) == Some(&"via");
+    let mov = match (words.first().copied()?, words.len()) {
+        ("move", 3) => PureMove::NormalMove(NormalMove::NonTamMoveSrcDst {
+            src: c(1)?,
+            dest: c(2)?,
+        }),
+        ("move", 5) if via(2) => PureMove::NormalMove(NormalMove::NonTamMoveSrcStepDstFinite {
+            src: c(1)?,
+            step: c(3)?,
+            dest: c(4)?,
+        }),
+        ("inf", 5) if via(2) => PureMove::InfAfterStep(InfAfterStep {
+            src: c(1)?,
+            step: c(3)?,
+            planned_direction: c(4)?,
+        }),
+        ("drop", 3) => {
+            let piece: NonTam2Piece = words[1].try_into().ok()?;
+            PureMove::NormalMove(NormalMove::NonTamMoveFromHopZuo {
+                color: piece.color,
+                prof: piece.prof,
+                dest: c(2)?,
+            })
+        }
+        ("tam", 4) => PureMove::NormalMove(NormalMove::TamMoveNoStep {
+            src: c(1)?,
+            first_dest: c(2)?,
+            second_dest: c(3)?,
+        }),
+        ("tam", 6) if via(2) => PureMove::NormalMove(NormalMove::TamMoveStepsDuringFormer {
+            src: c(1)?,
+            step: c(3)?,
+            first_dest: c(4)?,
+            second_dest: c(5)?,
+        }),
+        ("tam", 6) if via(3) => PureMove::NormalMove(NormalMove::TamMoveStepsDuringLatter {
+            src: c(1)?,
+            first_dest: c(2)?,
+            step: c(4)?,
+            second_dest: c(5)?,
+        }),
+        _ => return None,
+    };
+    Some(mov)
+}
+
+#[cfg(test)]
+mod tests {
+    use cetkaik_core::absolute::{NonTam2Piece, Side};
+    use cetkaik_core::{Color, Profession};
+    use cetkaik_full_state_transition::Config;
+
+    use super::{parse_move, serialize_move, serialize_position, Position};
+
+    #[test]
+    fn moves_round_trip() {
+        let config = Config::cerke_online_alpha();
+        let (mut state, _) = cetkaik_full_state_transition::initial_state().choose();
+        state.f.ia_side_hop1zuo1 = vec![NonTam2Piece {
+            color: Color::Kok1,
+            prof: Profession::Kauk2,
+        }];
+        state.f.a_side_hop1zuo1 = state.f.ia_side_hop1zuo1.clone();
+        for whose_turn in [Side::IASide, Side::ASide] {
+            state.whose_turn = whose_turn;
+            let (hop1zuo1_candidates, candidates) = state.get_candidates(config);
+            assert!(!hop1zuo1_candidates.is_empty());
+            for mov in hop1zuo1_candidates.iter().chain(&candidates) {
+                assert_eq!(Some(mov.clone()), parse_move(&serialize_move(mov)));
+            }
+        }
+        assert_eq!(None, parse_move("move LIA"));
+        assert_eq!(None, parse_move("tam ZO ZU ZY ZAI ZIA"));
+
+        let position = serialize_position(&Position::from(&state));
+        assert!(position.starts_with("turn A season 0 rate 1 scores 20 20 tam_moved false board "));
+        assert!(position.ends_with(" hop1zuo1 赤兵 赤兵"));
+    }
+}
@@ -0,0 +1,365 @@
+//! The strongest bot: a Monte Carlo tree search over the moves of both sides.
+//!
+//! Where the sticks will land is not known when a move is chosen, so the tree is open-loop:
+//! a node stands for a sequence of moves rather than for a position. Every iteration plays the
+//! sequence out again from the root, casting the sticks anew, so that the statistics of a node
+//! average over the ways they can fall. Playing every line out to the end of the game would cost
+//! far more than a server can spend on a move, so the search stops after [`SearchBudget::depth`]
+//! plies and scores the position from the scores and the captured pieces.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use cetkaik_core::absolute::{NonTam2Piece, Side};
+use cetkaik_full_state_transition::{
+    apply_after_half_acceptance, apply_inf_after_step, apply_normal_move,
+    message::PureMove,
+    resolve,
+    state::{GroundState, HandResolved},
+    Config, IfTaxot, Rate, Victor,
+};
+use rand::prelude::SliceRandom;
+
+use super::bot::{Bot, BotMove, BotMoveWithTactics};
+use super::heuristic::{self, hand_score, hop1zuo1_of, piece_value, POINT};
+use super::tymok::{decide, score_of, victory_of, HandDecision};
+use crate::types::bot::TacticsKey;
+
+/// How many of the candidates, the most promising first, are considered at each node
+const BRANCHING: usize = 12;
+
+/// Weighs trying the less visited moves against the ones that have done well so far
+const EXPLORATION: f64 = 1.0;
+
+/// A lead worth this much, on the scale of [`piece_value`], is taken as odds of about three to one
+const SCALE: f64 = 20.0;
+
+/// How much the search may spend on a single move. It stops at whichever limit it reaches first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchBudget {
+    pub iterations: usize,
+    pub time: Duration,
+
+    /// How many plies each iteration plays out before scoring the position
+    pub depth: usize,
+}
+
+impl Default for SearchBudget {
+    fn default() -> Self {
+        Self {
+            iterations: 2000,
+            time: Duration::from_secs(2),
+            depth: 4,
+        }
+    }
+}
+
+fn env_usize(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+impl SearchBudget {
+    /// Overrides the defaults with `BOT_SEARCH_ITERATIONS`, `BOT_SEARCH_MILLIS` and `BOT_SEARCH_DEPTH`.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut budget = Self::default();
+        if let Some(n) = env_usize("BOT_SEARCH_ITERATIONS") {
+            budget.iterations = n;
+        }
+        if let Some(millis) = env_usize("BOT_SEARCH_MILLIS") {
+            budget.time = Duration::from_millis(millis as u64);
+        }
+        if let Some(n) = env_usize("BOT_SEARCH_DEPTH") {
+            budget.depth = n;
+        }
+        budget
+    }
+}
+
+/// What `pieces` are worth to the side that has captured them, counting the hands they already make
+fn hop1zuo1_value(pieces: &[NonTam2Piece], rate: Rate) -> f64 {
+    pieces
+        .iter()
+        .map(|piece| piece_value(piece.prof))
+        .sum::<f64>()
+        + f64::from(hand_score(pieces) * rate.num()) * POINT
+}
+
+/// How good `state` looks for `me`, between 0 (lost) and 1 (won)
+fn static_value(state: &GroundState, me: Side) -> f64 {
+    let margin = f64::from(score_of(me, state.scores) - score_of(!me, state.scores)) * POINT
+        + hop1zuo1_value(hop1zuo1_of(state, me), state.rate)
+        - hop1zuo1_value(hop1zuo1_of(state, !me), state.rate);
+    1.0 / (1.0 + (-margin / SCALE).exp())
+}
+
+fn outcome(victor: Victor, me: Side) -> f64 {
+    if Some(victor) == victory_of(me) {
+        1.0
+    } else if Some(victor) == victory_of(!me) {
+        0.0
+    } else {
+        0.5
+    }
+}
+
+enum Step {
+    Continue(GroundState),
+
+    /// The game has ended, with this value for the side the search plays for
+    Over(f64),
+}
+
+/// Plays `mov` with the sticks cast at random. Whoever completes a hand decides as [`decide`] would.
+/// `None` if `mov` is not legal in `state`.
+fn play(state: &GroundState, mov: &PureMove, config: Config, me: Side) -> Option<Step> {
+    let after = match BotMove::from_strict_pure_move(mov) {
+        BotMove::NormalMove(mov) => apply_normal_move(state, mov, config).ok()?.choose().0,
+        BotMove::InfAfterStep { dat, after } => {
+            let (excited, ciurl) = apply_inf_after_step(state, dat, config).ok()?.choose();
+            apply_after_half_acceptance(&excited, after[ciurl.unwrap_or(0)], config)
+                .ok()?
+                .choose()
+                .0
+        }
+    };
+    Some(match resolve(&after, config) {
+        HandResolved::NeitherTymokNorTaxot(next) => Step::Continue(next),
+        HandResolved::HandExists { if_tymok, if_taxot } => match decide(&after, config) {
+            Some(HandDecision::TyMok) => Step::Continue(if_tymok),
+            Some(HandDecision::TaXot) | None => match if_taxot {
+                IfTaxot::NextSeason(next) => Step::Continue(next.choose().0),
+                IfTaxot::VictoriousSide(victor) => Step::Over(outcome(victor, me)),
+            },
+        },
+        HandResolved::GameEndsWithoutTymokTaxot(victor) => Step::Over(outcome(victor, me)),
+    })
+}
+
+/// The [`BRANCHING`] most promising candidates, in that order; those that look equally good are shuffled
+fn candidates(state: &GroundState, config: Config) -> Vec<(PureMove, Node)> {
+    let (hop1zuo1_candidates, candidates) = state.get_candidates(config);
+    let mut candidates: Vec<_> = hop1zuo1_candidates.into_iter().chain(candidates).collect();
+    candidates.shuffle(&mut rand::thread_rng());
+    let mut estimated: Vec<_> = candidates
+        .into_iter()
+        .map(|mov| {
+            let estimate = heuristic::estimate(state, &mov);
+            (mov, estimate)
+        })
+        .collect();
+    estimated.sort_by(|a, b| b.1.total_cmp(&a.1));
+    estimated
+        .into_iter()
+        .take(BRANCHING)
+        .map(|(mov, _)| (mov, Node::default()))
+        .collect()
+}
+
+#[derive(Default)]
+struct Node {
+    /// The moves considered from here, each with the node it leads to; `None` until the node is first passed through
+    children: Option<Vec<(PureMove, Node)>>,
+    visits: u32,
+
+    /// The sum of the values found below, for the side the search plays for
+    total: f64,
+}
+
+impl Node {
+    fn mean(&self) -> f64 {
+        self.total / f64::from(self.visits.max(1))
+    }
+
+    /// Upper confidence bound on the value of this node for the side choosing it
+    fn ucb(&self, maximizing: bool, ln_parent_visits: f64) -> f64 {
+        if self.visits == 0 {
+            return f64::INFINITY;
+        }
+        let mean = if maximizing {
+            self.mean()
+        } else {
+            1.0 - self.mean()
+        };
+        mean + EXPLORATION * (ln_parent_visits / f64::from(self.visits)).sqrt()
+    }
+
+    /// Plays one line out from `state`, `depth` plies at most, and returns its value for `me`
+    fn descend(&mut self, state: &GroundState, depth: usize, config: Config, me: Side) -> f64 {
+        if depth == 0 {
+            return static_value(state, me);
+        }
+        let maximizing = state.whose_turn == me;
+        let ln_visits = f64::from(self.visits.max(1)).ln();
+        let children = self
+            .children
+            .get_or_insert_with(|| candidates(state, config));
+
+        let mut chosen: Option<(&PureMove, &mut Node, f64)> = None;
+        for (mov, child) in children.iter_mut() {
+            let ucb = child.ucb(maximizing, ln_visits);
+            if chosen.as_ref().is_none_or(|(_, _, best)| ucb > *best) {
+                chosen = Some((mov, child, ucb));
+            }
+        }
+        let Some((mov, child, _)) = chosen else {
+            return static_value(state, me);
+        };
+
+        let value = match play(state, mov, config, me) {
+            // legal where the node was first reached, but the sticks have fallen differently this time
+            None => static_value(state, me),
+            Some(Step::Over(value)) => value,
+            Some(Step::Continue(next)) if child.visits == 0 => static_value(&next, me),
+            Some(Step::Continue(next)) => child.descend(&next, depth - 1, config, me),
+        };
+        child.visits += 1;
+        child.total += value;
+        self.visits += 1;
+        value
+    }
+}
+
+/// Plays the move most visited by the search, which runs for as long as `budget` allows,
+/// or until `stopped` is set. `None` if there is no move to make at all.
+#[must_use]
+pub fn bot_search(
+    game_state: &GroundState,
+    config: Config,
+    budget: SearchBudget,
+    stopped: &AtomicBool,
+) -> Option<BotMoveWithTactics> {
+    let me = game_state.whose_turn;
+    let started = Instant::now();
+    let mut root = Node::default();
+    let mut iterations = 0;
+    // at least once, so that there is a move to play however tight the budget
+    while iterations == 0
+        || (iterations < budget.iterations
+            && started.elapsed() < budget.time
+            && !stopped.load(Ordering::Relaxed))
+    {
+        root.descend(game_state, budget.depth.max(1), config, me);
+        iterations += 1;
+    }
+
+    let (mov, best) = root
+        .children?
+        .into_iter()
+        .max_by_key(|(_, child)| child.visits)?;
+    let tactics = if best.mean() >= 0.9 {
+        TacticsKey::VictoryAlmostCertain
+    } else if best.mean() <= 0.1 {
+        TacticsKey::LossAlmostCertain
+    } else {
+        heuristic::explain(game_state, &mov, config)
+    };
+    Some(BotMoveWithTactics {
+        tactics: Some(tactics),
+        mov,
+    })
+}
+
+/// Plays as [`bot_search`] does, within the given budget
+pub struct SearchBot {
+    budget: SearchBudget,
+    stopped: AtomicBool,
+}
+
+impl SearchBot {
+    #[must_use]
+    pub fn new(budget: SearchBudget) -> Self {
+        Self {
+            budget,
+            stopped: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Bot for SearchBot {
+    fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+        bot_search(state, config, self.budget, &self.stopped)
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    use cetkaik_core::absolute::{Column, Coord, Piece, Row, Side};
+    use cetkaik_core::{Color, Profession};
+    use cetkaik_full_state_transition::{
+        message::{NormalMove, PureMove},
+        Config, Rate, Scores,
+    };
+
+    use super::{bot_search, SearchBudget};
+    use crate::bot::fixtures::{board_from_pieces, piece};
+    use crate::types::bot::TacticsKey;
+
+    #[test]
+    fn finds_the_winning_capture() {
+        let io = Coord(Row::A, Column::K);
+        let their_io = Coord(Row::E, Column::K);
+        // taking the opponent's Io makes a hand worth 5, which is just enough to end the game
+        let state = board_from_pieces(
+            [
+                (io, piece(Color::Kok1, Profession::Io, Side::IASide)),
+                (their_io, piece(Color::Huok2, Profession::Io, Side::ASide)),
+                (Coord(Row::O, Column::Z), Piece::Tam2),
+            ],
+            Scores::new().edit(18, Side::IASide, Rate::X1).unwrap(),
+        );
+
+        let budget = SearchBudget {
+            iterations: 60,
+            time: Duration::from_mins(1),
+            depth: 2,
+        };
+        let chosen = bot_search(
+            &state,
+            Config::cerke_online_alpha(),
+            budget,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(Some(TacticsKey::VictoryAlmostCertain), chosen.tactics);
+        assert!(matches!(
+            chosen.mov,
+            PureMove::NormalMove(NormalMove::NonTamMoveSrcDst { src, dest }) if src == io && dest == their_io
+        ));
+    }
+
+    #[test]
+    fn stops_when_told() {
+        let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+        let budget = SearchBudget {
+            iterations: usize::MAX,
+            time: Duration::from_mins(10),
+            depth: 4,
+        };
+        let stopped = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let search = scope.spawn(|| {
+                bot_search(
+                    &initial_state,
+                    Config::cerke_online_alpha(),
+                    budget,
+                    &stopped,
+                )
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            let told = Instant::now();
+            stopped.store(true, Ordering::Relaxed);
+            assert!(search.join().unwrap().is_some());
+            assert!(told.elapsed() < Duration::from_secs(1));
+        });
+    }
+}
@@ -0,0 +1,194 @@
+//! The bot's turn is played on a thread of its own, on a copy of the game, so that a slow bot holds up only its own game
+//! rather than every request waiting on the lock of the rooms. The whole turn is played there: the move, where the piece goes
+//! once the sticks are cast, and the choice between ty mok and ta xot. The main poll starts the thinking, keeps answering
+//! `NotYetDetermined` until the turn is done, and then puts the copy in place of the game; see `AppState::reply_to_main_poll`.
+//! A turn is not done before the bot's think time is up, however quickly the bot has played it.
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::bot::{play_turn, Bot};
+use super::heuristic::HeuristicBot;
+use crate::types::{bot::TacticsKey, ApiError, ErrorCode, GameState, MoveToBePolled};
+
+/// The game with the bot's turn played on it, and the move as recorded
+pub type Turn = (
+    GameState,
+    Result<(MoveToBePolled, Option<TacticsKey>), ApiError>,
+);
+
+fn play_in_background(bot: Arc<dyn Bot>, mut game: GameState) -> Receiver<Turn> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let played = play_turn(bot.as_ref(), &mut game);
+        // nobody is waiting any more if the bot took too long
+        let _ = sender.send((game, played));
+    });
+    receiver
+}
+
+pub struct Thinking {
+    started_at: Instant,
+
+    /// How long after `started_at` the turn may be shown, as picked from the bot's `ThinkTime`
+    shown_after: Duration,
+
+    /// `GameState::move_count` when the thinking started, so that the turn is never played in another position
+    pub move_count: usize,
+
+    /// The game as it was when the thinking started, kept for when the bot has to be given up on
+    game: GameState,
+
+    /// The bot playing the turn, kept so that it can be told to stop once it is given up on
+    bot: Arc<dyn Bot>,
+    receiver: Receiver<Turn>,
+
+    /// When [`HeuristicBot`] took over the turn, if it has
+    gave_up_at: Option<Instant>,
+}
+
+impl Thinking {
+    #[must_use]
+    pub fn start(bot: Box<dyn Bot>, game: &GameState, shown_after: Duration) -> Self {
+        let bot: Arc<dyn Bot> = Arc::from(bot);
+        Self {
+            started_at: Instant::now(),
+            shown_after,
+            move_count: game.move_count(),
+            game: game.detached(),
+            receiver: play_in_background(Arc::clone(&bot), game.detached()),
+            bot,
+            gave_up_at: None,
+        }
+    }
+
+    /// The turn, once the bot has played it and `shown_after` has passed. `None` while the bot is still thinking.
+    /// Past `limit`, or if the thinking thread has panicked, the bot is told to stop and [`HeuristicBot`]
+    /// plays the turn instead, on a thread of its own as well and within the same `limit`.
+    pub fn result(&mut self, limit: Duration) -> Option<Turn> {
+        if self.started_at.elapsed() < self.shown_after {
+            return None;
+        }
+        let fallback = self.gave_up_at.is_some();
+        match self.receiver.try_recv() {
+            Ok(turn) => return Some(turn),
+            Err(TryRecvError::Empty) => {
+                let thinking_since = self.gave_up_at.unwrap_or(self.started_at);
+                if thinking_since.elapsed() < limit {
+                    return None;
+                }
+                if fallback {
+                    self.bot.stop();
+                    return Some(self.failed(format!(
+                        "the heuristic bot took longer than {limit:?} in place of the bot"
+                    )));
+                }
+                eprintln!("the bot took longer than {limit:?} to move; the heuristic bot plays in its place");
+            }
+            Err(TryRecvError::Disconnected) if fallback => {
+                return Some(self.failed("the heuristic bot panicked in place of the bot"));
+            }
+            Err(TryRecvError::Disconnected) => {
+                eprintln!("the bot panicked while thinking; the heuristic bot plays in its place");
+            }
+        }
+        self.bot.stop();
+        self.bot = Arc::new(HeuristicBot);
+        self.gave_up_at = Some(Instant::now());
+        self.receiver = play_in_background(Arc::clone(&self.bot), self.game.detached());
+        None
+    }
+
+    /// The game left as it was, with `why` the turn could not be played
+    fn failed(&self, why: impl Into<String>) -> Turn {
+        let error = ApiError::new(ErrorCode::InternalError, why);
+        (self.game.detached(), Err(error))
+    }
+}
+
+/// A thinking that is dropped before the bot is done, say because the room has gone, stops the bot
+/// rather than leaving it to search on for nobody.
+impl Drop for Thinking {
+    fn drop(&mut self) {
+        self.bot.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use cetkaik_full_state_transition::{
+        message::{AfterHalfAcceptance, InfAfterStep, PureMove},
+        state::{ExcitedState, GroundState, HandNotResolved},
+        Config,
+    };
+
+    use super::Thinking;
+    use crate::bot::bot::{Bot, BotMoveWithTactics};
+    use crate::bot::heuristic::HeuristicBot;
+    use crate::bot::tymok::HandDecision;
+    use crate::types::GameState;
+
+    /// Steps over a piece and casts the sticks, and then never makes up its mind about where to go
+    struct Stuck;
+
+    impl Bot for Stuck {
+        fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+            let (_, candidates) = state.get_candidates(config);
+            let mov = candidates
+                .into_iter()
+                .find(|mov| matches!(mov, PureMove::InfAfterStep(_)))?;
+            Some(BotMoveWithTactics { tactics: None, mov })
+        }
+
+        fn choose_after_half_acceptance(
+            &self,
+            _: InfAfterStep,
+            _: &ExcitedState,
+            _: Config,
+        ) -> AfterHalfAcceptance {
+            std::thread::sleep(Duration::from_mins(1));
+            AfterHalfAcceptance { dest: None }
+        }
+
+        fn choose_hand_decision(&self, _: &HandNotResolved, _: Config) -> HandDecision {
+            std::thread::sleep(Duration::from_mins(1));
+            HandDecision::TaXot
+        }
+    }
+
+    /// Polls `thinking` until the turn is done, as the main poll does
+    fn wait(thinking: &mut Thinking, limit: Duration) -> super::Turn {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(turn) = thinking.result(limit) {
+                return turn;
+            }
+            assert!(Instant::now() < deadline, "the turn was never played");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn the_turn_waits_for_the_think_time_and_not_past_the_limit() {
+        let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+        let game = GameState::new(initial_state, Config::cerke_online_alpha());
+
+        let mut thinking = Thinking::start(Box::new(Stuck), &game, Duration::ZERO);
+        let (played, recorded) = wait(&mut thinking, Duration::from_secs(1));
+        assert!(recorded.is_ok());
+        assert_eq!(1, played.move_count());
+        // the game that the thinking started from is left alone
+        assert_eq!(0, game.move_count());
+
+        // a turn played at once is held back until the think time is up
+        let mut thinking =
+            Thinking::start(Box::new(HeuristicBot), &game, Duration::from_millis(200));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(thinking.result(Duration::from_secs(5)).is_none());
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(thinking.result(Duration::from_secs(5)).is_some());
+    }
+}
@@ -0,0 +1,144 @@
+//! Whether the bot, having completed a hand, continues the season (ty mok) or ends it (ta xot).
+//!
+//! Ta xot banks the hand at the current rate. Ty mok banks nothing yet and doubles the rate,
+//! which pays off if the bot completes another hand first, and backfires if the opponent does.
+
+use cetkaik_core::absolute::Side;
+use cetkaik_full_state_transition::{
+    resolve, state::HandNotResolved, state::HandResolved, Config, IfTaxot, Rate, Scores, Victor,
+};
+
+/// A hand worth at least this much (before the rate is applied) is banked rather than gambled with.
+const BIG_HAND: i32 = 7;
+
+/// Once the rate has doubled this many times over, another ty mok is deemed too risky.
+const HIGH_RATE: i32 = 4;
+
+/// A score at which the lead is wide enough that ending the season is always worth it.
+const COMFORTABLE_SCORE: i32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandDecision {
+    TyMok,
+    TaXot,
+}
+
+/// `Victor` can neither be constructed nor inspected outside of `cetkaik_full_state_transition`,
+/// so the one meaning "`side` has won" is obtained by handing `side` all the points.
+#[must_use]
+pub fn victory_of(side: Side) -> Option<Victor> {
+    Scores::new().edit(20, side, Rate::X1).err()
+}
+
+pub(crate) fn score_of(side: Side, scores: Scores) -> i32 {
+    match side {
+        Side::IASide => scores.ia(),
+        Side::ASide => scores.a(),
+    }
+}
+
+/// Decides on behalf of the side that has just moved. `None` if the move completed no hand,
+/// in which case there is nothing to decide.
+#[must_use]
+pub fn decide(state: &HandNotResolved, config: Config) -> Option<HandDecision> {
+    let HandResolved::HandExists { if_taxot, .. } = resolve(state, config) else {
+        return None;
+    };
+    let me = state.whose_turn;
+
+    let after_taxot = match if_taxot {
+        // ending the game is only worth it if it is a win
+        IfTaxot::VictoriousSide(victor) => {
+            return Some(if Some(victor) == victory_of(me) {
+                HandDecision::TaXot
+            } else {
+                HandDecision::TyMok
+            });
+        }
+        IfTaxot::NextSeason(next) => score_of(me, next.choose().0.scores),
+    };
+    let before = score_of(me, state.scores);
+    let raw_gain = (after_taxot - before) / state.rate.num();
+
+    Some(if raw_gain <= 0 {
+        // a hand made only of penalties costs nothing as long as the season goes on
+        HandDecision::TyMok
+    } else if after_taxot >= COMFORTABLE_SCORE
+        || raw_gain >= BIG_HAND
+        || state.rate.num() >= HIGH_RATE
+    {
+        HandDecision::TaXot
+    } else if after_taxot < 20 {
+        // still behind even with this hand, so it is worth betting on the next one
+        HandDecision::TyMok
+    } else {
+        HandDecision::TaXot
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cetkaik_core::absolute::{NonTam2Piece, Side};
+    use cetkaik_core::{Color, Profession};
+    use cetkaik_full_state_transition::{state::HandNotResolved, Config, Rate, Scores, Season};
+
+    use super::{decide, HandDecision};
+
+    /// The IA side has just taken the opponent's Io, which alone makes a hand worth 5.
+    fn io_taken(ia_score: i32, season: Season, rate: Rate) -> HandNotResolved {
+        let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+        let mut f = initial_state.f;
+        f.ia_side_hop1zuo1 = vec![NonTam2Piece {
+            color: Color::Kok1,
+            prof: Profession::Io,
+        }];
+        HandNotResolved {
+            previous_a_side_hop1zuo1: vec![],
+            previous_ia_side_hop1zuo1: vec![],
+            f,
+            whose_turn: Side::IASide,
+            season,
+            scores: Scores::new()
+                .edit(ia_score - 20, Side::IASide, Rate::X1)
+                .unwrap(),
+            rate,
+            i_have_moved_tam_in_this_turn: false,
+            kut2tam2_happened: false,
+            tam2tysak2_raw_penalty: 0,
+            tam2tysak2_will_trigger_taxottymok: false,
+        }
+    }
+
+    #[test]
+    fn tymok_or_taxot() {
+        let config = Config::cerke_online_alpha();
+        let decide = |state: HandNotResolved| decide(&state, config).unwrap();
+
+        assert_eq!(
+            HandDecision::TaXot,
+            decide(io_taken(20, Season::Iei2, Rate::X1))
+        );
+        assert_eq!(
+            HandDecision::TyMok,
+            decide(io_taken(10, Season::Iei2, Rate::X1))
+        );
+        assert_eq!(
+            HandDecision::TaXot,
+            decide(io_taken(10, Season::Iei2, Rate::X4))
+        );
+        // ending the game with a win
+        assert_eq!(
+            HandDecision::TaXot,
+            decide(io_taken(36, Season::Iei2, Rate::X1))
+        );
+        // ending the game with a loss
+        assert_eq!(
+            HandDecision::TyMok,
+            decide(io_taken(10, Season::Iat1, Rate::X1))
+        );
+
+        let mut no_hand = io_taken(20, Season::Iei2, Rate::X1);
+        no_hand.previous_ia_side_hop1zuo1 = no_hand.f.ia_side_hop1zuo1.clone();
+        assert_eq!(None, super::decide(&no_hand, config));
+    }
+}
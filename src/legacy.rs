@@ -0,0 +1,535 @@
+//! Endpoints without the `/v2/` prefix, as consumed by the deployed `cerke_online_alpha` frontend.
+//! They run the same logic as their `/v2/` counterparts, but their responses are converted
+//! into the shapes below, which must never change.
+
+use crate::types::{self, bot::TacticsKey, AppState, Ciurl, MoveToBePolled, WhoGoesFirst};
+use crate::types::{
+    AfterHalfAcceptanceMessageStruct, MainMessage, MainMessageStruct, MsgWithAccessToken,
+    RetRandomEntry,
+};
+use actix_web::{post, web, HttpResponse, Responder};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use serde::Serialize;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(mainpoll)
+        .service(infpoll)
+        .service(whethertymok_tymok)
+        .service(whethertymok_taxot)
+        .service(whethertymokpoll)
+        .service(decision_main)
+        .service(slow2)
+        .service(decision_normalmove)
+        .service(decision_infafterstep)
+        .service(crate::random_entry)
+        .service(random_poll)
+        .service(random_cancel)
+        .service(crate::random_entry_staging)
+        .service(random_poll_staging)
+        .service(random_cancel_staging)
+        .service(crate::vs_cpu_entry_staging)
+        .service(crate::vs_cpu_entry);
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetTyMok {
+    Err,
+    Ok,
+}
+
+impl From<types::RetTyMok> for RetTyMok {
+    fn from(ret: types::RetTyMok) -> Self {
+        match ret {
+            types::RetTyMok::Err(_) => RetTyMok::Err,
+            types::RetTyMok::Ok => RetTyMok::Ok,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetTaXot {
+    Err,
+    Ok {
+        is_first_move_my_move: Option<WhoGoesFirst>,
+    },
+}
+
+impl From<types::RetTaXot> for RetTaXot {
+    fn from(ret: types::RetTaXot) -> Self {
+        match ret {
+            types::RetTaXot::Err(_) => RetTaXot::Err,
+            types::RetTaXot::Ok {
+                is_first_move_my_move,
+            } => RetTaXot::Ok {
+                is_first_move_my_move,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetInfAfterStep {
+    Ok { ciurl: Ciurl },
+    Err { why_illegal: String },
+}
+
+impl From<types::RetInfAfterStep> for RetInfAfterStep {
+    fn from(ret: types::RetInfAfterStep) -> Self {
+        match ret {
+            types::RetInfAfterStep::Ok { ciurl } => RetInfAfterStep::Ok { ciurl },
+            types::RetInfAfterStep::Err(e) => RetInfAfterStep::Err {
+                why_illegal: e.why_illegal,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetNormalMove {
+    Err { why_illegal: String },
+    WithWaterEntry { ciurl: Ciurl },
+    WithoutWaterEntry,
+}
+
+impl From<types::RetNormalMove> for RetNormalMove {
+    fn from(ret: types::RetNormalMove) -> Self {
+        match ret {
+            types::RetNormalMove::Err(e) => RetNormalMove::Err {
+                why_illegal: e.why_illegal,
+            },
+            types::RetNormalMove::WithWaterEntry { ciurl } => {
+                RetNormalMove::WithWaterEntry { ciurl }
+            }
+            types::RetNormalMove::WithoutWaterEntry => RetNormalMove::WithoutWaterEntry,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetAfterHalfAcceptance {
+    Err { why_illegal: String },
+    WithWaterEntry { ciurl: Ciurl },
+    WithoutWaterEntry,
+}
+
+impl From<types::RetAfterHalfAcceptance> for RetAfterHalfAcceptance {
+    fn from(ret: types::RetAfterHalfAcceptance) -> Self {
+        match ret {
+            types::RetAfterHalfAcceptance::Err(e) => RetAfterHalfAcceptance::Err {
+                why_illegal: e.why_illegal,
+            },
+            types::RetAfterHalfAcceptance::WithWaterEntry { ciurl } => {
+                RetAfterHalfAcceptance::WithWaterEntry { ciurl }
+            }
+            types::RetAfterHalfAcceptance::WithoutWaterEntry => {
+                RetAfterHalfAcceptance::WithoutWaterEntry
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetRandomPoll {
+    Err { why_illegal: String },
+    Ok { ret: RetRandomEntry },
+}
+
+impl From<types::RetRandomPoll> for RetRandomPoll {
+    fn from(ret: types::RetRandomPoll) -> Self {
+        match ret {
+            types::RetRandomPoll::Err(e) => RetRandomPoll::Err {
+                why_illegal: e.why_illegal,
+            },
+            types::RetRandomPoll::Ok { ret } => RetRandomPoll::Ok { ret },
+        }
+    }
+}
+
+impl RetRandomPoll {
+    /// The legacy frontend was always handed back the very token it polled with,
+    /// and the token issued on entry stays good once a room is assigned.
+    fn keeping(access_token: &str, ret: types::RetRandomPoll) -> Self {
+        match Self::from(ret) {
+            RetRandomPoll::Ok {
+                ret:
+                    RetRandomEntry::RoomAlreadyAssigned {
+                        is_first_move_my_move,
+                        is_ia_down_for_me,
+                        ..
+                    },
+            } => RetRandomPoll::Ok {
+                ret: RetRandomEntry::RoomAlreadyAssigned {
+                    access_token: access_token.to_owned(),
+                    is_first_move_my_move,
+                    is_ia_down_for_me,
+                },
+            },
+            ret => ret,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetRandomCancel {
+    Err { why_illegal: String },
+    Ok { cancellable: bool },
+}
+
+impl From<types::RetRandomCancel> for RetRandomCancel {
+    fn from(ret: types::RetRandomCancel) -> Self {
+        match ret {
+            types::RetRandomCancel::Err(e) => RetRandomCancel::Err {
+                why_illegal: e.why_illegal,
+            },
+            types::RetRandomCancel::Ok { cancellable } => RetRandomCancel::Ok { cancellable },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetWhetherTyMokPoll {
+    TyMok,
+    TaXot {
+        is_first_move_my_move: Option<WhoGoesFirst>,
+    },
+    NotYetDetermined,
+    Err {
+        why_illegal: String,
+    },
+}
+
+impl From<types::RetWhetherTyMokPoll> for RetWhetherTyMokPoll {
+    fn from(ret: types::RetWhetherTyMokPoll) -> Self {
+        match ret {
+            types::RetWhetherTyMokPoll::TyMok => RetWhetherTyMokPoll::TyMok,
+            types::RetWhetherTyMokPoll::TaXot {
+                is_first_move_my_move,
+            } => RetWhetherTyMokPoll::TaXot {
+                is_first_move_my_move,
+            },
+            types::RetWhetherTyMokPoll::NotYetDetermined => RetWhetherTyMokPoll::NotYetDetermined,
+            types::RetWhetherTyMokPoll::Err(e) => RetWhetherTyMokPoll::Err {
+                why_illegal: e.why_illegal,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetMainPoll {
+    MoveMade {
+        content: MoveToBePolled,
+        message: Option<TacticsKey>,
+    },
+    NotYetDetermined,
+    Err {
+        why_illegal: String,
+    },
+}
+
+impl From<types::RetMainPoll> for RetMainPoll {
+    fn from(ret: types::RetMainPoll) -> Self {
+        match ret {
+            types::RetMainPoll::MoveMade {
+                content, message, ..
+            } => RetMainPoll::MoveMade { content, message },
+            types::RetMainPoll::NotYetDetermined => RetMainPoll::NotYetDetermined,
+            types::RetMainPoll::Err(e) => RetMainPoll::Err {
+                why_illegal: e.why_illegal,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum RetInfPoll {
+    MoveMade { content: MoveToBePolled },
+    NotYetDetermined,
+    Err { why_illegal: String },
+}
+
+impl From<types::RetInfPoll> for RetInfPoll {
+    fn from(ret: types::RetInfPoll) -> Self {
+        match ret {
+            types::RetInfPoll::MoveMade { content } => RetInfPoll::MoveMade { content },
+            types::RetInfPoll::NotYetDetermined => RetInfPoll::NotYetDetermined,
+            types::RetInfPoll::Err(e) => RetInfPoll::Err {
+                why_illegal: e.why_illegal,
+            },
+        }
+    }
+}
+
+#[post("/poll/main")]
+async fn mainpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
+    HttpResponse::Ok().json(RetMainPoll::from(crate::main_poll_(auth.token(), &data)))
+}
+
+#[post("/poll/inf")]
+async fn infpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
+    HttpResponse::Ok().json(RetInfPoll::from(crate::inf_poll_(auth.token(), &data)))
+}
+
+#[post("/decision/tymok")]
+async fn whethertymok_tymok(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
+    HttpResponse::Ok().json(RetTyMok::from(crate::whethertymok_tymok_(
+        auth.token(),
+        &data,
+    )))
+}
+
+#[post("/decision/taxot")]
+async fn whethertymok_taxot(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
+    HttpResponse::Ok().json(RetTaXot::from(crate::whethertymok_taxot_(
+        auth.token(),
+        &data,
+    )))
+}
+
+#[post("/poll/whethertymok")]
+async fn whethertymokpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
+    HttpResponse::Ok().json(RetWhetherTyMokPoll::from(crate::whethertymokpoll_(
+        auth.token(),
+        &data,
+    )))
+}
+
+#[post("/decision/main")]
+async fn decision_main(
+    data: web::Data<AppState>,
+    message: web::Json<MainMessage>,
+    auth: BearerAuth,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetNormalMove::from(crate::slow_(
+        auth.token(),
+        &data,
+        &message,
+    )))
+}
+
+#[post("/decision/afterhalfacceptance")]
+async fn slow2(
+    data: web::Data<AppState>,
+    message: web::Json<AfterHalfAcceptanceMessageStruct>,
+    auth: BearerAuth,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetAfterHalfAcceptance::from(crate::slow2_(
+        auth.token(),
+        &data,
+        &message,
+    )))
+}
+
+#[post("/decision/infafterstep")]
+async fn decision_infafterstep(
+    data: web::Data<AppState>,
+    message: web::Json<MainMessageStruct>,
+    auth: BearerAuth,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetInfAfterStep::from(crate::decision_infafterstep_(
+        auth.token(),
+        &data,
+        &message,
+    )))
+}
+
+#[post("/decision/normalmove")]
+async fn decision_normalmove(
+    data: web::Data<AppState>,
+    message: web::Json<MainMessageStruct>,
+    auth: BearerAuth,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetNormalMove::from(crate::decision_normalmove_(
+        auth.token(),
+        &data,
+        &message,
+    )))
+}
+
+#[post("/matching/random/poll")]
+async fn random_poll(
+    msg: web::Json<MsgWithAccessToken>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetRandomPoll::keeping(
+        &msg.access_token,
+        crate::matching::random_entrance_poll_(false, &msg, &data),
+    ))
+}
+
+#[post("/matching/random/poll/staging")]
+async fn random_poll_staging(
+    msg: web::Json<MsgWithAccessToken>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetRandomPoll::keeping(
+        &msg.access_token,
+        crate::matching::random_entrance_poll_(true, &msg, &data),
+    ))
+}
+
+#[post("/matching/random/cancel")]
+async fn random_cancel(
+    msg: web::Json<MsgWithAccessToken>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetRandomCancel::from(
+        crate::matching::random_entrance_cancel(false, &msg, &data),
+    ))
+}
+
+#[post("/matching/random/cancel/staging")]
+async fn random_cancel_staging(
+    msg: web::Json<MsgWithAccessToken>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(RetRandomCancel::from(
+        crate::matching::random_entrance_cancel(true, &msg, &data),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RetInfPoll, RetMainPoll, RetNormalMove, RetRandomCancel, RetRandomPoll, RetTaXot, RetTyMok,
+        RetWhetherTyMokPoll,
+    };
+    use crate::types::{
+        self, bot::TacticsKey, ApiError, ErrorCode, MoveToBePolled, NonTamMoveDotData,
+        RetRandomEntry, RetVsCpuEntry, WhoGoesFirst,
+    };
+    use big_s::S;
+    use cetkaik_core::absolute::{Column, Coord, Row};
+
+    fn legacy_json<T: serde::Serialize>(ret: T) -> String {
+        serde_json::to_string(&ret).unwrap()
+    }
+
+    fn not_your_turn() -> ApiError {
+        ApiError::new(ErrorCode::NotYourTurn, "it's not your turn")
+    }
+
+    #[test]
+    fn main_poll_contract() {
+        let content = MoveToBePolled::NonTamMove {
+            data: NonTamMoveDotData::SrcDst {
+                src: Coord(Row::AI, Column::C),
+                dest: Coord(Row::Y, Column::C),
+                water_entry_ciurl: None,
+            },
+        };
+        assert_eq!(
+            r#"{"type":"MoveMade","content":{"type":"NonTamMove","data":{"type":"SrcDst","src":["AI","C"],"dest":["Y","C"]}},"message":"free_lunch"}"#,
+            legacy_json(RetMainPoll::from(types::RetMainPoll::MoveMade {
+                content,
+                message: Some(TacticsKey::FreeLunch),
+                localized_message: Some(S("A free lunch!")),
+            }))
+        );
+        assert_eq!(
+            r#"{"type":"NotYetDetermined"}"#,
+            legacy_json(RetMainPoll::from(types::RetMainPoll::NotYetDetermined))
+        );
+        assert_eq!(
+            r#"{"type":"Err","why_illegal":"it's not your turn"}"#,
+            legacy_json(RetMainPoll::from(types::RetMainPoll::Err(not_your_turn())))
+        );
+        assert_eq!(
+            r#"{"type":"Err","why_illegal":"it's not your turn"}"#,
+            legacy_json(RetInfPoll::from(types::RetInfPoll::Err(not_your_turn())))
+        );
+    }
+
+    #[test]
+    fn decision_contract() {
+        assert_eq!(
+            r#"{"type":"Err"}"#,
+            legacy_json(RetTyMok::from(types::RetTyMok::Err(not_your_turn())))
+        );
+        assert_eq!(
+            r#"{"type":"Ok"}"#,
+            legacy_json(RetTyMok::from(types::RetTyMok::Ok))
+        );
+        assert_eq!(
+            r#"{"type":"Err"}"#,
+            legacy_json(RetTaXot::from(types::RetTaXot::Err(not_your_turn())))
+        );
+        assert_eq!(
+            r#"{"type":"Ok","is_first_move_my_move":null}"#,
+            legacy_json(RetTaXot::from(types::RetTaXot::Ok {
+                is_first_move_my_move: None
+            }))
+        );
+        assert_eq!(
+            r#"{"type":"Err","why_illegal":"it's not your turn"}"#,
+            legacy_json(RetNormalMove::from(types::RetNormalMove::Err(
+                not_your_turn()
+            )))
+        );
+        assert_eq!(
+            r#"{"type":"WithoutWaterEntry"}"#,
+            legacy_json(RetNormalMove::from(types::RetNormalMove::WithoutWaterEntry))
+        );
+        assert_eq!(
+            r#"{"type":"Err","why_illegal":"it's not your turn"}"#,
+            legacy_json(RetWhetherTyMokPoll::from(types::RetWhetherTyMokPoll::Err(
+                not_your_turn()
+            )))
+        );
+    }
+
+    #[test]
+    fn matching_contract() {
+        let access_token = S("8a2d5f3e-0f5b-4c8b-9f1e-2f7e0c6a1b2c");
+        let who_goes_first = WhoGoesFirst {
+            result: true,
+            process: vec![],
+        };
+        assert_eq!(
+            r#"{"type":"InWaitingList","access_token":"8a2d5f3e-0f5b-4c8b-9f1e-2f7e0c6a1b2c"}"#,
+            legacy_json(RetRandomEntry::InWaitingList {
+                access_token: access_token.clone()
+            })
+        );
+        assert_eq!(
+            r#"{"type":"Ok","ret":{"type":"LetTheGameBegin","access_token":"8a2d5f3e-0f5b-4c8b-9f1e-2f7e0c6a1b2c","is_first_move_my_move":{"result":true,"process":[]},"is_IA_down_for_me":false}}"#,
+            legacy_json(RetRandomPoll::from(types::RetRandomPoll::Ok {
+                ret: RetRandomEntry::RoomAlreadyAssigned {
+                    access_token: access_token.clone(),
+                    is_first_move_my_move: who_goes_first.clone(),
+                    is_ia_down_for_me: false,
+                }
+            }))
+        );
+        assert_eq!(
+            r#"{"type":"Err","why_illegal":"access token could not be parsed"}"#,
+            legacy_json(RetRandomPoll::from(types::RetRandomPoll::Err(
+                ErrorCode::UnparsableToken.into()
+            )))
+        );
+        assert_eq!(
+            r#"{"type":"Ok","cancellable":true}"#,
+            legacy_json(RetRandomCancel::from(types::RetRandomCancel::Ok {
+                cancellable: true
+            }))
+        );
+        assert_eq!(
+            r#"{"type":"LetTheGameBegin","access_token":"8a2d5f3e-0f5b-4c8b-9f1e-2f7e0c6a1b2c","is_first_move_my_move":{"result":true,"process":[]},"is_IA_down_for_me":true}"#,
+            legacy_json(RetVsCpuEntry::LetTheGameBegin {
+                access_token,
+                is_first_move_my_move: who_goes_first,
+                is_ia_down_for_me: true,
+            })
+        );
+    }
+}
@@ -0,0 +1,10 @@
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::missing_panics_doc,
+    clippy::missing_errors_doc,
+    clippy::module_name_repetitions
+)]
+
+pub mod bot;
+pub mod types;
+pub mod typescript;
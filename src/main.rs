@@ -6,27 +6,40 @@
     clippy::module_name_repetitions
 )]
 
-pub mod bot;
+pub mod legacy;
 pub mod matching;
-pub mod types;
+pub mod openapi;
+pub mod rate_limit;
+pub mod spectate;
+
+use cerke_online_backend_rewritten::bot::BotSettings;
+use cerke_online_backend_rewritten::types;
 
 use crate::types::{
-    AccessToken, AfterHalfAcceptanceMessageStruct, AppState, MainMessage, MainMessageStruct,
-    MsgWithAccessToken, RetAfterHalfAcceptance, RetInfPoll, RetMainPoll, RetNormalMove, RetTaXot,
-    RetTyMok, RetWhetherTyMokPoll, RoomInfoWithPerspective,
+    AfterHalfAcceptanceMessageStruct, ApiError, AppState, ChatContentStruct, ErrorCode, Locale,
+    Localize, LockOrRecover, MainMessage, MainMessageStruct, MsgChatPoll, MsgWithAccessToken,
+    MsgWithRoomId, RetAfterHalfAcceptance, RetChatPoll, RetGameRecord, RetInfPoll, RetLegalMoves,
+    RetMainPoll, RetNormalMove, RetRoomState, RetSendChat, RetSpectatorPoll, RetTaXot, RetTyMok,
+    RetUnlistRoom, RetValidate, RetWhetherTyMokPoll, RoomId, RoomInfoWithPerspective,
+    SpectatorToken, TokenSigner,
 };
 use actix_cors::Cors;
 use actix_web::http::header;
-use actix_web::{post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use rate_limit::{RateLimit, RateLimitConfig, RateLimiter};
 use std::collections::{HashMap, HashSet};
-use std::{env, sync::Mutex};
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
 use types::RetInfAfterStep;
+use uuid::Uuid;
 
 async fn index(data: web::Data<AppState>) -> String {
-    let mut counter = data.access_counter.lock().unwrap();
+    let mut counter = data.access_counter.lock_or_recover();
     *counter += 1;
-    format!("Request number: {}", counter)
+    format!("Request number: {counter}")
 }
 
 #[actix_web::main]
@@ -38,14 +51,27 @@ async fn main() -> std::io::Result<()> {
     let app_state = web::Data::new(AppState {
         access_counter: Mutex::new(0),
         waiting_list: Mutex::new(HashSet::new()),
+        unlisted_on_entry: Mutex::new(HashSet::new()),
         person_to_room: Mutex::new(HashMap::new()),
+        spectator_to_room: Mutex::new(HashMap::new()),
         rooms_where_opponent_is_bot: Mutex::new(HashSet::new()),
+        room_to_bot_difficulty: Mutex::new(HashMap::new()),
+        unlisted_rooms: Mutex::new(HashSet::new()),
+        room_to_chat: Mutex::new(HashMap::new()),
+        chat_rate_limit: Mutex::new(HashMap::new()),
         room_to_gamestate: Mutex::new(HashMap::new()),
+        token_signer: TokenSigner::from_env(),
+        bot_settings: BotSettings::from_env(),
+        bot_thinking: Mutex::new(HashMap::new()),
+        ended_games: Mutex::new(HashMap::new()),
     });
 
+    let rate_limiter = Arc::new(RateLimiter::new(RateLimitConfig::from_env()));
+
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_methods(vec!["GET", "POST"])
+            .expose_headers(vec![header::RETRY_AFTER])
             .allowed_headers(vec![
                 header::ORIGIN,
                 header::CONTENT_TYPE,
@@ -61,88 +87,138 @@ async fn main() -> std::io::Result<()> {
         };
 
         App::new()
+            .wrap(RateLimit(rate_limiter.clone()))
             .wrap(cors)
             .app_data(app_state.clone())
             .route("/", web::get().to(index))
-            .service(mainpoll)
-            .service(infpoll)
-            .service(whethertymok_tymok)
-            .service(whethertymok_taxot)
-            .service(whethertymokpoll)
-            .service(decision_main)
-            .service(slow2)
-            .service(decision_normalmove)
-            .service(decision_infafterstep)
-            .service(random_entry)
-            .service(random_poll)
-            .service(random_cancel)
-            .service(random_entry_staging)
-            .service(random_poll_staging)
-            .service(random_cancel_staging)
-            .service(vs_cpu_entry_staging)
-            .service(vs_cpu_entry)
+            .configure(legacy::routes)
+            .configure(unversioned_routes)
+            .service(web::scope("/v2").configure(v2_routes))
     })
-    .bind(format!("0.0.0.0:{}", port))?
+    .bind(format!("0.0.0.0:{port}"))?
     .run()
     .await
 }
 
+/// Every endpoint, with the current response shapes.
+fn v2_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(mainpoll)
+        .service(infpoll)
+        .service(whethertymok_tymok)
+        .service(whethertymok_taxot)
+        .service(whethertymokpoll)
+        .service(decision_main)
+        .service(slow2)
+        .service(decision_normalmove)
+        .service(decision_infafterstep)
+        .service(decision_validate)
+        .service(random_entry)
+        .service(random_poll)
+        .service(random_cancel)
+        .service(random_entry_staging)
+        .service(random_poll_staging)
+        .service(random_cancel_staging)
+        .service(vs_cpu_entry_staging)
+        .service(vs_cpu_entry)
+        .service(spectate_entry)
+        .service(spectatorpoll)
+        .service(live_rooms)
+        .service(unlist_room)
+        .service(chat_send)
+        .service(chatpoll)
+        .service(game_record)
+        .service(room_state)
+        .service(legal_moves);
+}
+
+/// Endpoints that the legacy frontend never used; they stay reachable without the prefix too.
+fn unversioned_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(openapi_json)
+        .service(decision_validate)
+        .service(spectate_entry)
+        .service(spectatorpoll)
+        .service(live_rooms)
+        .service(unlist_room)
+        .service(chat_send)
+        .service(chatpoll)
+        .service(game_record)
+        .service(room_state)
+        .service(legal_moves);
+}
+
+#[get("/openapi.json")]
+async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(openapi::document())
+}
+
 #[post("/poll/main")]
-async fn mainpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
-    HttpResponse::Ok().json(main_poll_(auth.token(), &data))
+async fn mainpoll(data: web::Data<AppState>, auth: BearerAuth, locale: Locale) -> impl Responder {
+    HttpResponse::Ok().json(main_poll_(auth.token(), &data).localize(locale))
 }
 
 fn main_poll_(raw_token: &str, data: &web::Data<AppState>) -> RetMainPoll {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetMainPoll::Err { why_illegal },
+        Err(e) => RetMainPoll::Err(e),
         Ok(room_info) => data.reply_to_main_poll(&room_info),
     }
 }
 
 #[post("/poll/inf")]
-async fn infpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
-    HttpResponse::Ok().json(inf_poll_(auth.token(), &data))
+async fn infpoll(data: web::Data<AppState>, auth: BearerAuth, locale: Locale) -> impl Responder {
+    HttpResponse::Ok().json(inf_poll_(auth.token(), &data).localize(locale))
 }
 
 fn inf_poll_(raw_token: &str, data: &web::Data<AppState>) -> RetInfPoll {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetInfPoll::Err { why_illegal },
+        Err(e) => RetInfPoll::Err(e),
         Ok(room_info) => data.reply_to_inf_poll(&room_info),
     }
 }
 
 #[post("/decision/tymok")]
-async fn whethertymok_tymok(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
-    HttpResponse::Ok().json(whethertymok_tymok_(auth.token(), &data))
+async fn whethertymok_tymok(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(whethertymok_tymok_(auth.token(), &data).localize(locale))
 }
 
 fn whethertymok_tymok_(raw_token: &str, data: &web::Data<AppState>) -> RetTyMok {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(_why_illegal) => RetTyMok::Err,
+        Err(e) => RetTyMok::Err(e),
         Ok(room_info) => data.receive_tymok_and_update(&room_info),
     }
 }
 
 #[post("/decision/taxot")]
-async fn whethertymok_taxot(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
-    HttpResponse::Ok().json(whethertymok_taxot_(auth.token(), &data))
+async fn whethertymok_taxot(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(whethertymok_taxot_(auth.token(), &data).localize(locale))
 }
 
 fn whethertymok_taxot_(raw_token: &str, data: &web::Data<AppState>) -> RetTaXot {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(_why_illegal) => RetTaXot::Err,
+        Err(e) => RetTaXot::Err(e),
         Ok(room_info) => data.receive_taxot_and_update(&room_info),
     }
 }
 
 #[post("/poll/whethertymok")]
-async fn whethertymokpoll(data: web::Data<AppState>, auth: BearerAuth) -> impl Responder {
-    HttpResponse::Ok().json(whethertymokpoll_(auth.token(), &data))
+async fn whethertymokpoll(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(whethertymokpoll_(auth.token(), &data).localize(locale))
 }
 
 fn whethertymokpoll_(raw_token: &str, data: &web::Data<AppState>) -> RetWhetherTyMokPoll {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetWhetherTyMokPoll::Err { why_illegal },
+        Err(e) => RetWhetherTyMokPoll::Err(e),
         Ok(room_info) => data.reply_to_whether_tymok_poll(&room_info),
     }
 }
@@ -152,8 +228,9 @@ async fn decision_main(
     data: web::Data<AppState>,
     message: web::Json<MainMessage>,
     auth: BearerAuth,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(slow_(auth.token(), &data, &message))
+    HttpResponse::Ok().json(slow_(auth.token(), &data, &message).localize(locale))
 }
 
 #[post("/decision/afterhalfacceptance")]
@@ -161,24 +238,67 @@ async fn slow2(
     data: web::Data<AppState>,
     message: web::Json<AfterHalfAcceptanceMessageStruct>,
     auth: BearerAuth,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(slow2_(auth.token(), &data, &message))
+    HttpResponse::Ok().json(slow2_(auth.token(), &data, &message).localize(locale))
 }
 
 fn parse_token_and_get_room_info(
     raw_token: &str,
     data: &web::Data<AppState>,
-) -> Result<RoomInfoWithPerspective, String> {
-    match AccessToken::parse_str(raw_token) {
-        Err(e) => Err(format!(
-            "Unparsable access token `{}`; failed because of {}",
-            raw_token, e
+) -> Result<RoomInfoWithPerspective, ApiError> {
+    if let Ok(uuid) = Uuid::parse_str(raw_token) {
+        let spectator_to_room = data.spectator_to_room.lock_or_recover();
+        return Err(if spectator_to_room.contains_key(&SpectatorToken(uuid)) {
+            ApiError::new(
+                ErrorCode::ReadOnlyToken,
+                format!("Spectator token `{raw_token}` is read-only and cannot be used here"),
+            )
+        } else {
+            ApiError::new(
+                ErrorCode::UnparsableToken,
+                format!("`{raw_token}` is a bare UUID; access tokens are now signed, so please re-enter"),
+            )
+        });
+    }
+
+    let claims = data.token_signer.verify(raw_token)?;
+    let room_info = {
+        let person_to_room = data.person_to_room.lock_or_recover();
+        match person_to_room.get(&claims.access_token()) {
+            // a token issued while waiting stands for the room it was matched into
+            Some(room_info) if claims.room_id().is_none() || claims.matches(room_info) => {
+                (*room_info).clone()
+            }
+            _ => {
+                return Err(ApiError::new(
+                    ErrorCode::UnknownToken,
+                    format!("Unrecognized access token `{raw_token}`"),
+                ))
+            }
+        }
+    };
+    data.check_token_not_revoked(room_info.room_id)?;
+    Ok(room_info)
+}
+
+fn parse_spectator_token_and_get_room_id(
+    raw_token: &str,
+    data: &web::Data<AppState>,
+) -> Result<RoomId, ApiError> {
+    match SpectatorToken::parse_str(raw_token) {
+        Err(e) => Err(ApiError::new(
+            ErrorCode::UnparsableToken,
+            format!("Unparsable spectator token `{raw_token}`; failed because of {e}"),
         )),
-        Ok(access_token) => {
-            let person_to_room = data.person_to_room.lock().unwrap();
-            match person_to_room.get(&access_token) {
-                None => Err(format!("Unrecognized access token `{}`", raw_token)),
-                Some(room_info) => Ok((*room_info).clone()),
+        Ok(spectator_token) => {
+            let spectator_to_room = data.spectator_to_room.lock_or_recover();
+            match spectator_to_room.get(&spectator_token) {
+                None => Err(ApiError::new(
+                    ErrorCode::UnknownToken,
+                    format!("Unrecognized spectator token `{raw_token}`"),
+                )),
+                Some(room_id) => Ok(*room_id),
             }
         }
     }
@@ -190,7 +310,7 @@ fn slow_(
     message: &web::Json<MainMessage>,
 ) -> RetNormalMove {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetNormalMove::Err { why_illegal },
+        Err(e) => RetNormalMove::Err(e),
         Ok(room_info) => data.analyze_main_message_and_update(**message, &room_info),
     }
 }
@@ -201,7 +321,7 @@ fn slow2_(
     message: &web::Json<AfterHalfAcceptanceMessageStruct>,
 ) -> RetAfterHalfAcceptance {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetAfterHalfAcceptance::Err { why_illegal },
+        Err(e) => RetAfterHalfAcceptance::Err(e),
         Ok(room_info) => {
             data.analyze_afterhalfacceptance_message_and_update(message.message, &room_info)
         }
@@ -213,8 +333,9 @@ async fn decision_infafterstep(
     data: web::Data<AppState>,
     message: web::Json<MainMessageStruct>,
     auth: BearerAuth,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(decision_infafterstep_(auth.token(), &data, &message))
+    HttpResponse::Ok().json(decision_infafterstep_(auth.token(), &data, &message).localize(locale))
 }
 
 fn decision_infafterstep_(
@@ -223,7 +344,7 @@ fn decision_infafterstep_(
     message: &web::Json<MainMessageStruct>,
 ) -> RetInfAfterStep {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetInfAfterStep::Err { why_illegal },
+        Err(e) => RetInfAfterStep::Err(e),
         Ok(room_info) => data.analyze_inf_after_step_and_update(message.message, &room_info),
     }
 }
@@ -233,8 +354,9 @@ async fn decision_normalmove(
     data: web::Data<AppState>,
     message: web::Json<MainMessageStruct>,
     auth: BearerAuth,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(decision_normalmove_(auth.token(), &data, &message))
+    HttpResponse::Ok().json(decision_normalmove_(auth.token(), &data, &message).localize(locale))
 }
 
 fn decision_normalmove_(
@@ -243,59 +365,1096 @@ fn decision_normalmove_(
     message: &web::Json<MainMessageStruct>,
 ) -> RetNormalMove {
     match parse_token_and_get_room_info(raw_token, data) {
-        Err(why_illegal) => RetNormalMove::Err { why_illegal },
+        Err(e) => RetNormalMove::Err(e),
         Ok(room_info) => data.analyze_main_message_and_update(message.message, &room_info),
     }
 }
 
+#[post("/decision/validate")]
+async fn decision_validate(
+    data: web::Data<AppState>,
+    message: web::Json<MainMessageStruct>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(decision_validate_(auth.token(), &data, &message).localize(locale))
+}
+
+fn decision_validate_(
+    raw_token: &str,
+    data: &web::Data<AppState>,
+    message: &web::Json<MainMessageStruct>,
+) -> RetValidate {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetValidate::Err(e),
+        Ok(room_info) => data.validate_main_message(message.message, &room_info),
+    }
+}
+
+/// The body of an entry is optional, since older clients send none; without one, the defaults are used.
+/// A body that is there but cannot be read is refused rather than taken for the defaults.
+fn body_or_default<T: Default + serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    if body.trim_ascii().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| {
+        ApiError::new(
+            ErrorCode::InvalidRequest,
+            format!("the request body could not be read: {e}"),
+        )
+    })
+}
+
+fn invalid_request(error: ApiError, locale: Locale) -> HttpResponse {
+    let error = error.localize(locale);
+    HttpResponse::BadRequest().json(serde_json::json!({
+        "type": "Err",
+        "code": error.code,
+        "why_illegal": error.why_illegal,
+    }))
+}
+
 #[post("/matching/random/entry")]
-async fn random_entry(data: web::Data<AppState>) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entry_(false, &data))
+async fn random_entry(body: web::Bytes, data: web::Data<AppState>, locale: Locale) -> HttpResponse {
+    match body_or_default(&body) {
+        Ok(msg) => HttpResponse::Ok().json(matching::random_entry_(false, msg, &data)),
+        Err(e) => invalid_request(e, locale),
+    }
 }
 
 #[post("/matching/random/entry/staging")]
-async fn random_entry_staging(data: web::Data<AppState>) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entry_(true, &data))
+async fn random_entry_staging(
+    body: web::Bytes,
+    data: web::Data<AppState>,
+    locale: Locale,
+) -> HttpResponse {
+    match body_or_default(&body) {
+        Ok(msg) => HttpResponse::Ok().json(matching::random_entry_(true, msg, &data)),
+        Err(e) => invalid_request(e, locale),
+    }
 }
 
 #[post("/matching/random/poll")]
 async fn random_poll(
     msg: web::Json<MsgWithAccessToken>,
     data: web::Data<AppState>,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entrance_poll_(false, &msg, &data))
+    HttpResponse::Ok().json(matching::random_entrance_poll_(false, &msg, &data).localize(locale))
 }
 
 #[post("/matching/random/poll/staging")]
 async fn random_poll_staging(
     msg: web::Json<MsgWithAccessToken>,
     data: web::Data<AppState>,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entrance_poll_(true, &msg, &data))
+    HttpResponse::Ok().json(matching::random_entrance_poll_(true, &msg, &data).localize(locale))
 }
 
 #[post("/matching/random/cancel")]
 async fn random_cancel(
     msg: web::Json<MsgWithAccessToken>,
     data: web::Data<AppState>,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entrance_cancel(false, &msg, &data))
+    HttpResponse::Ok().json(matching::random_entrance_cancel(false, &msg, &data).localize(locale))
 }
 
 #[post("/matching/random/cancel/staging")]
 async fn random_cancel_staging(
     msg: web::Json<MsgWithAccessToken>,
     data: web::Data<AppState>,
+    locale: Locale,
 ) -> impl Responder {
-    HttpResponse::Ok().json(matching::random_entrance_cancel(true, &msg, &data))
+    HttpResponse::Ok().json(matching::random_entrance_cancel(true, &msg, &data).localize(locale))
 }
 
 #[post("/matching/vs_cpu/entry")]
-async fn vs_cpu_entry(data: web::Data<AppState>) -> impl Responder {
-    HttpResponse::Ok().json(matching::vs_cpu_entry_(false, &data))
+async fn vs_cpu_entry(body: web::Bytes, data: web::Data<AppState>, locale: Locale) -> HttpResponse {
+    match body_or_default(&body) {
+        Ok(msg) => HttpResponse::Ok().json(matching::vs_cpu_entry_(false, msg, &data)),
+        Err(e) => invalid_request(e, locale),
+    }
 }
 
 #[post("/matching/vs_cpu/entry/staging")]
-async fn vs_cpu_entry_staging(data: web::Data<AppState>) -> impl Responder {
-    HttpResponse::Ok().json(matching::vs_cpu_entry_(true, &data))
+async fn vs_cpu_entry_staging(
+    body: web::Bytes,
+    data: web::Data<AppState>,
+    locale: Locale,
+) -> HttpResponse {
+    match body_or_default(&body) {
+        Ok(msg) => HttpResponse::Ok().json(matching::vs_cpu_entry_(true, msg, &data)),
+        Err(e) => invalid_request(e, locale),
+    }
+}
+
+#[post("/spectate/entry")]
+async fn spectate_entry(
+    msg: web::Json<MsgWithRoomId>,
+    data: web::Data<AppState>,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(spectate::spectate_entry_(&msg, &data).localize(locale))
+}
+
+#[post("/poll/spectate")]
+async fn spectatorpoll(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(spectator_poll_(auth.token(), &data).localize(locale))
+}
+
+fn spectator_poll_(raw_token: &str, data: &web::Data<AppState>) -> RetSpectatorPoll {
+    match parse_spectator_token_and_get_room_id(raw_token, data) {
+        Err(e) => RetSpectatorPoll::Err(e),
+        Ok(room_id) => data.reply_to_spectator_poll(room_id),
+    }
+}
+
+#[get("/rooms/live")]
+async fn live_rooms(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.list_live_rooms())
+}
+
+#[post("/room/unlist")]
+async fn unlist_room(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(unlist_room_(auth.token(), &data).localize(locale))
+}
+
+fn unlist_room_(raw_token: &str, data: &web::Data<AppState>) -> RetUnlistRoom {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetUnlistRoom::Err(e),
+        Ok(room_info) => data.unlist_room(&room_info),
+    }
+}
+
+#[post("/chat/send")]
+async fn chat_send(
+    data: web::Data<AppState>,
+    message: web::Json<ChatContentStruct>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(chat_send_(auth.token(), &data, message.into_inner()).localize(locale))
+}
+
+fn chat_send_(
+    raw_token: &str,
+    data: &web::Data<AppState>,
+    message: ChatContentStruct,
+) -> RetSendChat {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetSendChat::Err(e),
+        Ok(room_info) => data.receive_chat_and_update(message.message, &room_info),
+    }
+}
+
+#[post("/poll/chat")]
+async fn chatpoll(
+    data: web::Data<AppState>,
+    message: web::Json<MsgChatPoll>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(chat_poll_(auth.token(), &data, &message).localize(locale))
+}
+
+fn chat_poll_(
+    raw_token: &str,
+    data: &web::Data<AppState>,
+    message: &web::Json<MsgChatPoll>,
+) -> RetChatPoll {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetChatPoll::Err(e),
+        Ok(room_info) => data.reply_to_chat_poll(message.since, &room_info),
+    }
+}
+
+#[post("/room/record")]
+async fn game_record(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(game_record_(auth.token(), &data).localize(locale))
+}
+
+fn game_record_(raw_token: &str, data: &web::Data<AppState>) -> RetGameRecord {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetGameRecord::Err(e),
+        Ok(room_info) => data.export_game_record(&room_info),
+    }
+}
+
+#[post("/room/state")]
+async fn room_state(data: web::Data<AppState>, auth: BearerAuth, locale: Locale) -> impl Responder {
+    HttpResponse::Ok().json(room_state_(auth.token(), &data).localize(locale))
+}
+
+fn room_state_(raw_token: &str, data: &web::Data<AppState>) -> RetRoomState {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetRoomState::Err(e),
+        Ok(room_info) => data.reply_to_room_state(&room_info),
+    }
+}
+
+#[post("/room/legal_moves")]
+async fn legal_moves(
+    data: web::Data<AppState>,
+    auth: BearerAuth,
+    locale: Locale,
+) -> impl Responder {
+    HttpResponse::Ok().json(legal_moves_(auth.token(), &data).localize(locale))
+}
+
+fn legal_moves_(raw_token: &str, data: &web::Data<AppState>) -> RetLegalMoves {
+    match parse_token_and_get_room_info(raw_token, data) {
+        Err(e) => RetLegalMoves::Err(e),
+        Ok(room_info) => data.reply_to_legal_moves(&room_info),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        bot::BotDifficulty, MainMessage, MsgRandomEntry, MsgVsCpuEntry, NonTamMoveDotData,
+        RetSpectateEntry,
+    };
+    use cerke_online_backend_rewritten::bot::{bot::BotMoveWithTactics, tymok::HandDecision, Bot};
+    use cetkaik_core::{Color, Profession};
+    use cetkaik_full_state_transition::{
+        message::{AfterHalfAcceptance, InfAfterStep, NormalMove, PureMove},
+        state::{ExcitedState, GroundState, HandNotResolved},
+        Config,
+    };
+
+    #[test]
+    fn spectator_token_is_rejected_by_decision_endpoints() {
+        use cetkaik_core::absolute::{Column, Coord, Row};
+        let data = web::Data::new(AppState::default());
+        let _ = matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        let room_id = data
+            .room_to_gamestate
+            .lock()
+            .unwrap()
+            .keys()
+            .next()
+            .copied()
+            .unwrap();
+
+        let RetSpectateEntry::Ok { spectator_token } = spectate::spectate_entry_(
+            &web::Json(MsgWithRoomId {
+                room_id: room_id.to_string(),
+            }),
+            &data,
+        ) else {
+            panic!("spectating an existing room must succeed");
+        };
+
+        let message = web::Json(MainMessageStruct::from(MainMessage::NonTamMove {
+            data: NonTamMoveDotData::SrcDst {
+                src: Coord(Row::AI, Column::C),
+                dest: Coord(Row::Y, Column::C),
+                water_entry_ciurl: None,
+            },
+        }));
+        assert!(matches!(
+            decision_normalmove_(&spectator_token, &data, &message),
+            RetNormalMove::Err(ApiError {
+                code: ErrorCode::ReadOnlyToken,
+                ..
+            })
+        ));
+        assert!(matches!(
+            whethertymok_tymok_(&spectator_token, &data),
+            RetTyMok::Err(ApiError {
+                code: ErrorCode::ReadOnlyToken,
+                ..
+            })
+        ));
+        assert!(matches!(
+            whethertymok_taxot_(&spectator_token, &data),
+            RetTaXot::Err(ApiError {
+                code: ErrorCode::ReadOnlyToken,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            spectator_poll_(&spectator_token, &data),
+            RetSpectatorPoll::Ok {
+                current_season: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unlisted_room_is_not_shown_as_live() {
+        use crate::types::{RetLiveRooms, RetVsCpuEntry};
+        let data = web::Data::new(AppState::default());
+        let RetVsCpuEntry::LetTheGameBegin { access_token, .. } =
+            matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+
+        let RetLiveRooms::Ok { rooms } = data.list_live_rooms();
+        assert_eq!(1, rooms.len());
+        assert!(rooms[0].opponent_is_bot);
+        assert_eq!(
+            (20, 20),
+            (rooms[0].ia_owner_s_score, rooms[0].a_owner_s_score)
+        );
+
+        let room_id = rooms[0].room_id.to_string();
+        let spectate = || {
+            spectate::spectate_entry_(
+                &web::Json(MsgWithRoomId {
+                    room_id: room_id.clone(),
+                }),
+                &data,
+            )
+        };
+        let RetSpectateEntry::Ok { spectator_token } = spectate() else {
+            panic!("a listed room must be open to spectators");
+        };
+
+        assert_eq!(RetUnlistRoom::Ok, unlist_room_(&access_token, &data));
+        let RetLiveRooms::Ok { rooms } = data.list_live_rooms();
+        assert!(rooms.is_empty());
+        // spectators are sent away and kept away
+        assert!(matches!(
+            spectator_poll_(&spectator_token, &data),
+            RetSpectatorPoll::Err(_)
+        ));
+        assert!(matches!(spectate(), RetSpectateEntry::Err(_)));
+    }
+
+    #[test]
+    fn rooms_can_be_unlisted_from_entry() {
+        use crate::types::RetLiveRooms;
+        let data = web::Data::new(AppState::default());
+        let _ = matching::vs_cpu_entry_(
+            false,
+            MsgVsCpuEntry {
+                unlisted: true,
+                ..MsgVsCpuEntry::default()
+            },
+            &data,
+        );
+        // in random matching, it is enough for the player who waited to ask
+        let _ = matching::random_entry_(false, MsgRandomEntry { unlisted: true }, &data);
+        let _ = matching::random_entry_(false, MsgRandomEntry::default(), &data);
+        let room_ids: Vec<_> = data
+            .room_to_gamestate
+            .lock()
+            .unwrap()
+            .keys()
+            .copied()
+            .collect();
+        assert_eq!(2, room_ids.len());
+
+        let RetLiveRooms::Ok { rooms } = data.list_live_rooms();
+        assert!(rooms.is_empty());
+        for room_id in room_ids {
+            assert!(matches!(
+                spectate::spectate_entry_(
+                    &web::Json(MsgWithRoomId {
+                        room_id: room_id.to_string(),
+                    }),
+                    &data,
+                ),
+                RetSpectateEntry::Err(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn chat_is_rate_limited_and_recorded() {
+        use crate::types::{ChatContent, QuickPhrase, RetVsCpuEntry, CHAT_RATE_LIMIT_COUNT};
+        let data = web::Data::new(AppState::default());
+        let RetVsCpuEntry::LetTheGameBegin { access_token, .. } =
+            matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        let message = ChatContentStruct {
+            message: ChatContent::QuickPhrase {
+                phrase: QuickPhrase::Greetings,
+            },
+        };
+
+        for i in 0..CHAT_RATE_LIMIT_COUNT {
+            assert_eq!(
+                RetSendChat::Ok { id: i },
+                chat_send_(&access_token, &data, message.clone())
+            );
+        }
+        assert!(matches!(
+            chat_send_(&access_token, &data, message),
+            RetSendChat::Err { .. }
+        ));
+
+        let RetChatPoll::Ok { messages } =
+            chat_poll_(&access_token, &data, &web::Json(MsgChatPoll { since: 3 }))
+        else {
+            panic!("polling the chat of one's own room must succeed");
+        };
+        assert_eq!(CHAT_RATE_LIMIT_COUNT - 3, messages.len());
+
+        let RetGameRecord::Ok { chat, .. } = game_record_(&access_token, &data) else {
+            panic!("exporting the record of one's own room must succeed");
+        };
+        assert_eq!(CHAT_RATE_LIMIT_COUNT, chat.len());
+    }
+
+    #[test]
+    fn room_state_at_the_beginning() {
+        use crate::types::{PieceWithPerspective, RetVsCpuEntry};
+        let data = web::Data::new(AppState::default());
+        let RetVsCpuEntry::LetTheGameBegin {
+            access_token,
+            is_ia_down_for_me,
+            ..
+        } = matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+
+        let RetRoomState::Ok { state } = room_state_(&access_token, &data) else {
+            panic!("a player must be able to see the state of their own room");
+        };
+        assert_eq!(is_ia_down_for_me, state.is_ia_down_for_me);
+        assert_eq!(49, state.board.len());
+        assert_eq!(
+            24,
+            state
+                .board
+                .iter()
+                .filter(|p| matches!(
+                    p.piece,
+                    PieceWithPerspective::NonTam2Piece { is_mine: true, .. }
+                ))
+                .count()
+        );
+        assert!(state.my_hop1zuo1.is_empty() && state.opponent_hop1zuo1.is_empty());
+        assert_eq!(
+            (20, 20, 1),
+            (state.my_score, state.opponent_score, state.rate)
+        );
+        assert_eq!(None, state.pending_inf_after_step);
+    }
+
+    #[test]
+    fn tokens_are_bound_to_room_and_side() {
+        use crate::types::{RetVsCpuEntry, TokenClaims};
+        let data = web::Data::new(AppState::default());
+        let RetVsCpuEntry::LetTheGameBegin { access_token, .. } =
+            matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        let claims: TokenClaims = data.token_signer.verify(&access_token).unwrap();
+        assert!(parse_token_and_get_room_info(&access_token, &data).is_ok());
+
+        let other_side = data.token_signer.sign(&TokenClaims {
+            is_ia_down_for_me: claims.is_ia_down_for_me.map(|b| !b),
+            ..claims.clone()
+        });
+        assert_eq!(
+            ErrorCode::UnknownToken,
+            parse_token_and_get_room_info(&other_side, &data)
+                .unwrap_err()
+                .code
+        );
+
+        let expired = data.token_signer.sign(&TokenClaims { exp: 1, ..claims });
+        assert_eq!(
+            ErrorCode::ExpiredToken,
+            parse_token_and_get_room_info(&expired, &data)
+                .unwrap_err()
+                .code
+        );
+
+        let bare = data
+            .person_to_room
+            .lock()
+            .unwrap()
+            .keys()
+            .next()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            ErrorCode::UnparsableToken,
+            parse_token_and_get_room_info(&bare, &data)
+                .unwrap_err()
+                .code
+        );
+    }
+
+    #[test]
+    fn a_game_against_the_bot_does_not_fail() {
+        use crate::types::RetVsCpuEntry;
+        use rand::seq::SliceRandom;
+        let data = web::Data::new(without_think_time());
+        let RetVsCpuEntry::LetTheGameBegin { access_token, .. } =
+            matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            match legal_moves_(&access_token, &data) {
+                RetLegalMoves::Ok { candidates, .. } => {
+                    let candidates: Vec<_> = candidates
+                        .into_iter()
+                        .filter(|candidate| !matches!(candidate, MainMessage::InfAfterStep { .. }))
+                        .collect();
+                    let candidate = candidates.choose(&mut rng).unwrap();
+                    assert!(!matches!(
+                        slow_(&access_token, &data, &web::Json(*candidate)),
+                        RetNormalMove::Err(_)
+                    ));
+                }
+                RetLegalMoves::Err(ApiError {
+                    code: ErrorCode::WrongPhase,
+                    ..
+                }) => {
+                    let _ = whethertymok_tymok_(&access_token, &data);
+                }
+                RetLegalMoves::Err(_) => {
+                    let ret = main_poll_(&access_token, &data);
+                    assert!(!matches!(ret, RetMainPoll::Err(_)), "{ret:?}");
+                    // give the bot some time to think
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn vs_cpu_entry_takes_an_optional_difficulty() {
+        use actix_web::test::{call_service, init_service, TestRequest};
+        let data = web::Data::new(AppState::default());
+        let app = init_service(
+            App::new()
+                .app_data(data.clone())
+                .service(web::scope("/v2").configure(v2_routes)),
+        )
+        .await;
+
+        let without_body = TestRequest::post()
+            .uri("/v2/matching/vs_cpu/entry")
+            .to_request();
+        let with_body = TestRequest::post()
+            .uri("/v2/matching/vs_cpu/entry")
+            .set_json(MsgVsCpuEntry {
+                difficulty: BotDifficulty::Easy,
+                ..MsgVsCpuEntry::default()
+            })
+            .to_request();
+        for request in [without_body, with_body] {
+            assert!(call_service(&app, request).await.status().is_success());
+        }
+
+        // a body that is there but cannot be read starts no game
+        for body in [r#"{"difficulty":"hardd"}"#, "{"] {
+            let request = TestRequest::post()
+                .uri("/v2/matching/vs_cpu/entry")
+                .insert_header(header::ContentType::json())
+                .set_payload(body)
+                .to_request();
+            let response = call_service(&app, request).await;
+            assert_eq!(actix_web::http::StatusCode::BAD_REQUEST, response.status());
+            let response: serde_json::Value = actix_web::test::read_body_json(response).await;
+            assert_eq!("invalid_request", response["code"]);
+        }
+
+        let mut difficulties: Vec<_> = data
+            .room_to_bot_difficulty
+            .lock()
+            .unwrap()
+            .values()
+            .copied()
+            .collect();
+        difficulties.sort_by_key(|difficulty| *difficulty == BotDifficulty::Normal);
+        assert_eq!(
+            vec![BotDifficulty::Easy, BotDifficulty::Normal],
+            difficulties
+        );
+    }
+
+    #[actix_web::test]
+    async fn every_documented_endpoint_is_served() {
+        use actix_web::http::{Method, StatusCode};
+        use actix_web::test::{call_service, init_service, TestRequest};
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::default()))
+                .service(web::scope("/v2").configure(v2_routes)),
+        )
+        .await;
+
+        let document = openapi::document();
+        let paths = document["paths"].as_object().unwrap();
+        assert!(!paths.is_empty());
+        for (path, operations) in paths {
+            for method in operations.as_object().unwrap().keys() {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                let request = TestRequest::default()
+                    .method(method.clone())
+                    .uri(&format!("/v2{path}"))
+                    .to_request();
+                let status = call_service(&app, request).await.status();
+                assert!(
+                    status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                    "{method} /v2{path} is documented but not served"
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_legacy_frontend_plays_with_the_token_it_entered_with() {
+        use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+        use serde_json::{json, Value};
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::default()))
+                .configure(legacy::routes)
+                .configure(unversioned_routes),
+        )
+        .await;
+        let post = |uri: &str, token: Option<&str>, body: Value| {
+            let request = TestRequest::post().uri(uri).set_json(body);
+            match token {
+                Some(token) => {
+                    request.insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+                }
+                None => request,
+            }
+            .to_request()
+        };
+
+        let mut tokens = vec![];
+        for expected in ["InWaitingList", "LetTheGameBegin"] {
+            let request = post("/matching/random/entry", None, json!({}));
+            let entry: Value = read_body_json(call_service(&app, request).await).await;
+            assert_eq!(expected, entry["type"]);
+            tokens.push(entry["access_token"].as_str().unwrap().to_owned());
+        }
+        let request = post(
+            "/matching/random/poll",
+            None,
+            json!({ "access_token": tokens[0] }),
+        );
+        let poll: Value = read_body_json(call_service(&app, request).await).await;
+        assert_eq!("LetTheGameBegin", poll["ret"]["type"]);
+        assert_eq!(tokens[0], poll["ret"]["access_token"]);
+
+        let mut moved = 0;
+        for token in &tokens {
+            let request = post("/room/legal_moves", Some(token), json!({}));
+            let moves: Value = read_body_json(call_service(&app, request).await).await;
+            let Some(candidates) = moves["candidates"].as_array() else {
+                assert_eq!("not_your_turn", moves["code"]);
+                continue;
+            };
+            let candidate = candidates
+                .iter()
+                .find(|candidate| candidate["type"] != "InfAfterStep")
+                .unwrap();
+            let request = post("/decision/main", Some(token), candidate.clone());
+            let decision: Value = read_body_json(call_service(&app, request).await).await;
+            assert_ne!("Err", decision["type"], "{decision}");
+            moved += 1;
+        }
+        assert_eq!(1, moved);
+
+        for token in &tokens {
+            let request = post("/poll/main", Some(token), json!({}));
+            let poll: Value = read_body_json(call_service(&app, request).await).await;
+            assert_ne!("Err", poll["type"], "{poll}");
+        }
+    }
+
+    /// An `AppState` whose bots move as soon as they have found their move
+    fn without_think_time() -> AppState {
+        AppState {
+            bot_settings: BotSettings {
+                think_times: HashMap::new(),
+                ..BotSettings::default()
+            },
+            ..AppState::default()
+        }
+    }
+
+    /// Steps over a piece and casts the sticks, and then takes a minute to decide where the piece goes
+    struct Hesitant;
+
+    impl Bot for Hesitant {
+        fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+            let (_, candidates) = state.get_candidates(config);
+            let mov = candidates
+                .into_iter()
+                .find(|mov| matches!(mov, PureMove::InfAfterStep(_)))?;
+            Some(BotMoveWithTactics { tactics: None, mov })
+        }
+
+        fn choose_after_half_acceptance(
+            &self,
+            _: InfAfterStep,
+            _: &ExcitedState,
+            _: Config,
+        ) -> AfterHalfAcceptance {
+            std::thread::sleep(std::time::Duration::from_mins(1));
+            AfterHalfAcceptance { dest: None }
+        }
+    }
+
+    #[test]
+    fn a_bot_slow_to_finish_its_turn_holds_up_no_other_room() {
+        use crate::types::{Phase, RetVsCpuEntry};
+        use cerke_online_backend_rewritten::bot::thinking::Thinking;
+        use cetkaik_core::absolute::Side;
+        use std::time::{Duration, Instant};
+        let data = web::Data::new(without_think_time());
+        let RetVsCpuEntry::LetTheGameBegin {
+            access_token: slow,
+            is_ia_down_for_me,
+            ..
+        } = matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        {
+            let mut room_to_gamestate = data.room_to_gamestate.lock().unwrap();
+            let (room_id, game_state) = room_to_gamestate.iter_mut().next().unwrap();
+            let Phase::Start(state) = &mut game_state.state else {
+                unreachable!("no move has been made yet")
+            };
+            // hand the turn to the bot, which plays it as `Hesitant` does
+            state.whose_turn = if is_ia_down_for_me {
+                Side::ASide
+            } else {
+                Side::IASide
+            };
+            assert!(Hesitant.choose_move(state, game_state.config).is_some());
+            let thinking = Thinking::start(Box::new(Hesitant), game_state, Duration::ZERO);
+            data.bot_thinking.lock().unwrap().insert(*room_id, thinking);
+        }
+        // let the bot cast the sticks and get stuck deciding
+        std::thread::sleep(Duration::from_millis(100));
+
+        let started = Instant::now();
+        assert!(matches!(
+            main_poll_(&slow, &data),
+            RetMainPoll::NotYetDetermined
+        ));
+        let RetVsCpuEntry::LetTheGameBegin {
+            access_token: other,
+            ..
+        } = matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        assert!(!matches!(main_poll_(&other, &data), RetMainPoll::Err(_)));
+        let _ = legal_moves_(&other, &data);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    /// Takes the opponent's Io, which completes a hand, and then declares ty mok or ta xot as it is told
+    struct Decisive(HandDecision);
+
+    impl Bot for Decisive {
+        fn choose_move(&self, state: &GroundState, config: Config) -> Option<BotMoveWithTactics> {
+            let (_, candidates) = state.get_candidates(config);
+            let mov = candidates.into_iter().find(|mov| {
+                matches!(
+                    mov,
+                    PureMove::NormalMove(NormalMove::NonTamMoveSrcDst { dest, .. })
+                        if state.f.board.get(dest).is_some_and(|piece| piece.has_prof(Profession::Io))
+                )
+            })?;
+            Some(BotMoveWithTactics { tactics: None, mov })
+        }
+
+        fn choose_hand_decision(&self, _: &HandNotResolved, _: Config) -> HandDecision {
+            self.0
+        }
+    }
+
+    #[test]
+    fn the_bot_s_ty_mok_or_ta_xot_reaches_the_human() {
+        use crate::types::{Phase, RetVsCpuEntry, RetWhetherTyMokPoll};
+        use cerke_online_backend_rewritten::bot::thinking::Thinking;
+        use cetkaik_core::absolute::{Column, Coord, Field, Piece, Row, Side};
+        use cetkaik_full_state_transition::{Rate, Scores, Season};
+        use std::time::{Duration, Instant};
+
+        for decision in [HandDecision::TyMok, HandDecision::TaXot] {
+            let data = web::Data::new(without_think_time());
+            let RetVsCpuEntry::LetTheGameBegin {
+                access_token,
+                is_ia_down_for_me,
+                ..
+            } = matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+            let (human, bot) = if is_ia_down_for_me {
+                (Side::IASide, Side::ASide)
+            } else {
+                (Side::ASide, Side::IASide)
+            };
+            {
+                let mut room_to_gamestate = data.room_to_gamestate.lock().unwrap();
+                let (room_id, game_state) = room_to_gamestate.iter_mut().next().unwrap();
+                let io = |color, side| Piece::NonTam2Piece {
+                    color,
+                    prof: Profession::Io,
+                    side,
+                };
+                // the bot's Io stands next to the human's, and taking it makes a hand
+                game_state.state = Phase::Start(GroundState {
+                    f: Field {
+                        board: HashMap::from([
+                            (Coord(Row::A, Column::K), io(Color::Kok1, bot)),
+                            (Coord(Row::E, Column::K), io(Color::Huok2, human)),
+                            (Coord(Row::O, Column::Z), Piece::Tam2),
+                        ]),
+                        a_side_hop1zuo1: vec![],
+                        ia_side_hop1zuo1: vec![],
+                    },
+                    whose_turn: bot,
+                    season: Season::Iei2,
+                    scores: Scores::new(),
+                    rate: Rate::X1,
+                    tam_has_moved_previously: false,
+                });
+                let thinking =
+                    Thinking::start(Box::new(Decisive(decision)), game_state, Duration::ZERO);
+                data.bot_thinking.lock().unwrap().insert(*room_id, thinking);
+            }
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !matches!(
+                main_poll_(&access_token, &data),
+                RetMainPoll::MoveMade { .. }
+            ) {
+                assert!(Instant::now() < deadline, "the bot never moved");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            match (decision, whethertymokpoll_(&access_token, &data)) {
+                (HandDecision::TyMok, RetWhetherTyMokPoll::TyMok) => {}
+                (
+                    HandDecision::TaXot,
+                    RetWhetherTyMokPoll::TaXot {
+                        is_first_move_my_move: Some(first_mover),
+                    },
+                ) => {
+                    let room_to_gamestate = data.room_to_gamestate.lock().unwrap();
+                    let game_state = room_to_gamestate.values().next().unwrap();
+                    // the move that completed the hand was the last of the first season
+                    assert_eq!(1, game_state.state.get_season().to_index());
+                    assert!(game_state.moves_to_be_polled[1].is_empty());
+                    assert_eq!(game_state.state.whose_turn() == human, first_mover.result);
+                }
+                (decision, ret) => {
+                    panic!("the bot declared {decision:?}, but the poll said {ret:?}")
+                }
+            }
+        }
+    }
+
+    /// Pairs two human players through random matching and returns their signed tokens.
+    fn two_player_room(data: &web::Data<AppState>) -> (String, String) {
+        use crate::types::RetRandomEntry;
+        let RetRandomEntry::InWaitingList {
+            access_token: waiting_token,
+        } = matching::random_entry_(false, MsgRandomEntry::default(), data)
+        else {
+            panic!("the first player must be put on the waiting list");
+        };
+        let RetRandomEntry::RoomAlreadyAssigned { access_token, .. } =
+            matching::random_entry_(false, MsgRandomEntry::default(), data)
+        else {
+            panic!("the room must be assigned since someone was waiting");
+        };
+
+        // the token issued while waiting is good for the room as well, but the poll binds it to the room
+        assert!(parse_token_and_get_room_info(&waiting_token, data).is_ok());
+        let types::RetRandomPoll::Ok {
+            ret:
+                RetRandomEntry::RoomAlreadyAssigned {
+                    access_token: waiting_token,
+                    ..
+                },
+        } = matching::random_entrance_poll_(
+            false,
+            &web::Json(MsgWithAccessToken {
+                access_token: waiting_token,
+            }),
+            data,
+        )
+        else {
+            panic!("the waiting player must be told about the room");
+        };
+        (access_token, waiting_token)
+    }
+
+    #[test]
+    fn decisions_are_refused_out_of_turn_or_phase() {
+        use crate::types::AfterHalfAcceptanceMessage;
+        let data = web::Data::new(AppState::default());
+        let (a, b) = two_player_room(&data);
+        let (mover, waiter, candidates) = match legal_moves_(&a, &data) {
+            RetLegalMoves::Ok { candidates, .. } => (a, b, candidates),
+            RetLegalMoves::Err(_) => {
+                let RetLegalMoves::Ok { candidates, .. } = legal_moves_(&b, &data) else {
+                    panic!("one of the two must be able to move");
+                };
+                (b, a, candidates)
+            }
+        };
+
+        for candidate in candidates.into_iter().take(10) {
+            let message = web::Json(MainMessageStruct::from(candidate));
+            assert!(matches!(
+                slow_(&waiter, &data, &web::Json(candidate)),
+                RetNormalMove::Err(ApiError {
+                    code: ErrorCode::NotYourTurn,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                decision_infafterstep_(&waiter, &data, &message),
+                RetInfAfterStep::Err(ApiError {
+                    code: ErrorCode::NotYourTurn,
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            whethertymok_tymok_(&waiter, &data),
+            RetTyMok::Err(ApiError {
+                code: ErrorCode::NotYourTurn,
+                ..
+            })
+        ));
+        assert!(matches!(
+            whethertymok_taxot_(&waiter, &data),
+            RetTaXot::Err(ApiError {
+                code: ErrorCode::NotYourTurn,
+                ..
+            })
+        ));
+
+        let after_half = web::Json(AfterHalfAcceptanceMessageStruct {
+            message: AfterHalfAcceptanceMessage::AfterHalfAcceptance { dest: None },
+        });
+        assert!(matches!(
+            slow2_(&mover, &data, &after_half),
+            RetAfterHalfAcceptance::Err(ApiError {
+                code: ErrorCode::WrongPhase,
+                ..
+            })
+        ));
+        assert!(matches!(
+            whethertymok_tymok_(&mover, &data),
+            RetTyMok::Err(ApiError {
+                code: ErrorCode::WrongPhase,
+                ..
+            })
+        ));
+
+        let move_count = data
+            .room_to_gamestate
+            .lock()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap()
+            .move_count();
+        assert_eq!(0, move_count);
+    }
+
+    #[test]
+    fn legal_moves_only_on_my_turn() {
+        let data = web::Data::new(AppState::default());
+        let (access_token, waiting_token) = two_player_room(&data);
+
+        let results = [
+            legal_moves_(&access_token, &data),
+            legal_moves_(&waiting_token, &data),
+        ];
+        let mut candidates_of_the_player_to_move =
+            results.into_iter().filter_map(|ret| match ret {
+                RetLegalMoves::Ok {
+                    hop1zuo1_candidates,
+                    candidates,
+                } => {
+                    assert!(hop1zuo1_candidates.is_empty());
+                    Some(candidates)
+                }
+                RetLegalMoves::Err { .. } => None,
+            });
+        let candidates = candidates_of_the_player_to_move.next().unwrap();
+        assert!(candidates_of_the_player_to_move.next().is_none());
+
+        assert!(!candidates.is_empty());
+        for candidate in candidates {
+            let json = serde_json::to_string(&MainMessageStruct::from(candidate)).unwrap();
+            let round_trip: MainMessageStruct = serde_json::from_str(&json).unwrap();
+            assert_eq!(candidate, round_trip.message);
+        }
+    }
+
+    #[test]
+    fn validation_does_not_mutate_the_game() {
+        use crate::types::{RetVsCpuEntry, TamMoveInternal};
+        use cetkaik_core::absolute::{Column, Coord, Row};
+        let data = web::Data::new(without_think_time());
+        let RetVsCpuEntry::LetTheGameBegin { access_token, .. } =
+            matching::vs_cpu_entry_(false, MsgVsCpuEntry::default(), &data);
+        // let the bot move first if it is its turn; it thinks in the background, so poll until it has
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while matches!(
+            legal_moves_(&access_token, &data),
+            RetLegalMoves::Err { .. }
+        ) {
+            assert!(std::time::Instant::now() < deadline, "the bot never moved");
+            let _ = main_poll_(&access_token, &data);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let RetLegalMoves::Ok { candidates, .. } = legal_moves_(&access_token, &data) else {
+            panic!("it must be the human's turn after the bot has moved");
+        };
+        let move_count = data
+            .room_to_gamestate
+            .lock()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap()
+            .move_count();
+
+        for candidate in candidates.into_iter().take(10) {
+            assert!(matches!(
+                decision_validate_(&access_token, &data, &web::Json(candidate.into())),
+                RetValidate::Legal { .. }
+            ));
+        }
+        let nonsense = MainMessage::TamMove {
+            flatten: TamMoveInternal::NoStep {
+                src: Coord(Row::A, Column::K),
+                first_dest: Coord(Row::A, Column::L),
+                second_dest: Coord(Row::A, Column::N),
+            },
+        };
+        assert!(matches!(
+            decision_validate_(&access_token, &data, &web::Json(nonsense.into())),
+            RetValidate::Illegal { .. }
+        ));
+
+        assert_eq!(
+            move_count,
+            data.room_to_gamestate
+                .lock()
+                .unwrap()
+                .values()
+                .next()
+                .unwrap()
+                .move_count()
+        );
+    }
 }
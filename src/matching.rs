@@ -1,12 +1,9 @@
-use std::sync::{Arc, Mutex};
-
-use crate::types::{AccessToken, AppState, MsgWithAccessToken};
+use crate::types::{AccessToken, ApiError, AppState, ErrorCode, LockOrRecover, MsgWithAccessToken};
 use crate::types::{
-    BotToken, GameState, Phase, RetRandomCancel, RetRandomEntry, RetRandomPoll, RetVsCpuEntry,
-    RoomId, RoomInfoWithPerspective,
+    BotToken, GameState, MsgRandomEntry, MsgVsCpuEntry, RetRandomCancel, RetRandomEntry,
+    RetRandomPoll, RetVsCpuEntry, RoomId, RoomInfoWithPerspective,
 };
 use actix_web::web;
-use big_s::S;
 use cetkaik_core::absolute::Side;
 use uuid::Uuid;
 
@@ -16,46 +13,52 @@ pub fn random_entrance_poll_(
     msg: &web::Json<MsgWithAccessToken>,
     data: &web::Data<AppState>,
 ) -> RetRandomPoll {
-    if let Ok(access_token) = AccessToken::parse_str(&msg.access_token) {
-        let person_to_room = data.person_to_room.lock().unwrap();
-        if let Some(room_perspective) = (*person_to_room).get(&access_token) {
-            let gss = data.room_to_gamestate.lock().unwrap();
-            let game_state: &GameState = gss
-                .get(&room_perspective.room_id)
-                .expect("FIXME: cannot happen");
-            let is_first_move_my_move =
-                game_state.is_first_move_my_move(room_perspective.is_ia_down_for_me, 0);
-            // You already have a room
+    let claims = match data.token_signer.verify(&msg.access_token) {
+        Ok(claims) => claims,
+        Err(e) => return RetRandomPoll::Err(e),
+    };
+    let access_token = claims.access_token();
+    let person_to_room = data.person_to_room.lock_or_recover();
+    if let Some(room_perspective) = (*person_to_room).get(&access_token) {
+        let gss = data.room_to_gamestate.lock_or_recover();
+        let Some(is_first_move_my_move) =
+            gss.get(&room_perspective.room_id).and_then(|game_state| {
+                game_state.is_first_move_my_move(room_perspective.is_ia_down_for_me, 0)
+            })
+        else {
+            return RetRandomPoll::Err(ErrorCode::UnknownRoom.into());
+        };
+        // You already have a room; a token issued while waiting is swapped for one bound to the room
+        RetRandomPoll::Ok {
+            ret: RetRandomEntry::RoomAlreadyAssigned {
+                access_token: if claims.matches(room_perspective) {
+                    msg.access_token.clone()
+                } else {
+                    data.token_signer
+                        .issue(access_token, Some(room_perspective))
+                },
+                is_first_move_my_move,
+                is_ia_down_for_me: room_perspective.is_ia_down_for_me,
+            },
+        }
+    } else {
+        let waiting_list = data.waiting_list.lock_or_recover();
+        if (*waiting_list).contains(&access_token) {
+            // not yet assigned a room, but is in the waiting list
             RetRandomPoll::Ok {
-                ret: RetRandomEntry::RoomAlreadyAssigned {
-                    access_token: access_token.to_string(),
-                    is_first_move_my_move,
-                    is_ia_down_for_me: room_perspective.is_ia_down_for_me,
+                ret: RetRandomEntry::InWaitingList {
+                    access_token: msg.access_token.clone(),
                 },
             }
         } else {
-            let waiting_list = data.waiting_list.lock().unwrap();
-            if (*waiting_list).contains(&access_token) {
-                // not yet assigned a room, but is in the waiting list
-                RetRandomPoll::Ok {
-                    ret: RetRandomEntry::InWaitingList {
-                        access_token: access_token.to_string(),
-                    },
-                }
-            } else {
-                RetRandomPoll::Err {
-                    why_illegal: format!(
-                        r#"Invalid access token:
-I don't know {}, which is the access token that you sent me.
-Please reapply by sending an empty object to random/entry ."#,
-                        access_token
-                    ),
-                }
-            }
-        }
-    } else {
-        RetRandomPoll::Err {
-            why_illegal: S("access token could not be parsed"),
+            RetRandomPoll::Err(ApiError::new(
+                ErrorCode::UnknownToken,
+                format!(
+                    r"Invalid access token:
+I don't know {access_token}, which is the access token that you sent me.
+Please reapply by sending an empty object to random/entry ."
+                ),
+            ))
         }
     }
 }
@@ -81,20 +84,26 @@ impl<T> RemoveRandom for Vec<T> {
 }
 
 #[must_use]
-pub fn random_entry_(is_staging: bool, data: &web::Data<AppState>) -> RetRandomEntry {
+pub fn random_entry_(
+    is_staging: bool,
+    msg: MsgRandomEntry,
+    data: &web::Data<AppState>,
+) -> RetRandomEntry {
     use rand::Rng;
     let new_token = AccessToken(Uuid::new_v4());
     let mut rng = rand::thread_rng();
-    let mut waiting_list = data.waiting_list.lock().unwrap();
-    let mut person_to_room = data.person_to_room.lock().unwrap();
-    let mut room_to_gamestate = data.room_to_gamestate.lock().unwrap();
+    let mut waiting_list = data.waiting_list.lock_or_recover();
+    let mut person_to_room = data.person_to_room.lock_or_recover();
+    let mut room_to_gamestate = data.room_to_gamestate.lock_or_recover();
     let mut waiting_list_vec: Vec<AccessToken> = (*waiting_list).iter().copied().collect();
     let opt_token = waiting_list_vec.remove_random(&mut rng);
     if let Some(token) = opt_token {
         (*waiting_list).remove(&token);
         let room_id = open_a_room(token, new_token, is_staging);
-
-        let is_first_turn_newtoken_turn = Arc::new(Mutex::new([None, None, None, None]));
+        let waiting_unlisted = data.unlisted_on_entry.lock_or_recover().remove(&token);
+        if msg.unlisted || waiting_unlisted {
+            data.unlisted_rooms.lock_or_recover().insert(room_id);
+        }
 
         let is_ia_down_for_newtoken: bool = rng.gen();
         person_to_room.insert(
@@ -114,29 +123,34 @@ pub fn random_entry_(is_staging: bool, data: &web::Data<AppState>) -> RetRandomE
 
         let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
         let is_ia_start = initial_state.whose_turn == Side::IASide;
-        room_to_gamestate.insert(
-            room_id,
-            GameState {
-                state: Phase::Start(initial_state),
-                config: cetkaik_full_state_transition::Config::cerke_online_alpha(),
-                waiting_for_after_half_acceptance: None,
-                moves_to_be_polled: [vec![], vec![], vec![], vec![]],
-                is_first_move_ia_move: is_first_turn_newtoken_turn,
-            },
+        let mut game_state = GameState::new(
+            initial_state,
+            cetkaik_full_state_transition::Config::cerke_online_alpha(),
         );
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_id)
-            .expect("FIXME: cannot happen");
-        game_state.set_first_mover(0_usize, is_ia_start, &mut rng);
+        let is_first_move_ia_move = game_state.set_first_mover(0_usize, is_ia_start, &mut rng);
+        room_to_gamestate.insert(room_id, game_state);
 
         return RetRandomEntry::RoomAlreadyAssigned {
-            access_token: format!("{}", new_token),
-            is_first_move_my_move: game_state.is_first_move_my_move(is_ia_down_for_newtoken, 0),
+            access_token: data.token_signer.issue(
+                new_token,
+                Some(&RoomInfoWithPerspective {
+                    room_id,
+                    is_ia_down_for_me: is_ia_down_for_newtoken,
+                }),
+            ),
+            is_first_move_my_move: is_first_move_ia_move.seen_by(is_ia_down_for_newtoken),
             is_ia_down_for_me: is_ia_down_for_newtoken,
         };
     }
 
-    todo!()
+    // nobody to play against yet
+    waiting_list.insert(new_token);
+    if msg.unlisted {
+        data.unlisted_on_entry.lock_or_recover().insert(new_token);
+    }
+    RetRandomEntry::InWaitingList {
+        access_token: data.token_signer.issue(new_token, None),
+    }
 }
 
 #[must_use]
@@ -154,18 +168,21 @@ pub fn open_a_room_against_bot(
 }
 
 #[must_use]
-pub fn vs_cpu_entry_(is_staging: bool, data: &web::Data<AppState>) -> RetVsCpuEntry {
+pub fn vs_cpu_entry_(
+    is_staging: bool,
+    msg: MsgVsCpuEntry,
+    data: &web::Data<AppState>,
+) -> RetVsCpuEntry {
     use rand::Rng;
     let new_token = AccessToken(Uuid::new_v4());
     let bot_token = BotToken(Uuid::new_v4());
     let room_id = open_a_room_against_bot(bot_token, new_token, is_staging);
     let mut rng = rand::thread_rng();
-    let is_first_turn_newtoken_turn = Arc::new(Mutex::new([None, None, None, None]));
 
     let is_ia_down_for_newtoken: bool = rng.gen();
-    let mut person_to_room = data.person_to_room.lock().unwrap();
-    let mut room_to_gamestate = data.room_to_gamestate.lock().unwrap();
-    let mut rooms_where_opponent_is_bot = data.rooms_where_opponent_is_bot.lock().unwrap();
+    let mut person_to_room = data.person_to_room.lock_or_recover();
+    let mut room_to_gamestate = data.room_to_gamestate.lock_or_recover();
+    let mut rooms_where_opponent_is_bot = data.rooms_where_opponent_is_bot.lock_or_recover();
     person_to_room.insert(
         new_token,
         RoomInfoWithPerspective {
@@ -175,27 +192,30 @@ pub fn vs_cpu_entry_(is_staging: bool, data: &web::Data<AppState>) -> RetVsCpuEn
     );
 
     rooms_where_opponent_is_bot.insert(room_id);
+    data.room_to_bot_difficulty
+        .lock_or_recover()
+        .insert(room_id, msg.difficulty);
+    if msg.unlisted {
+        data.unlisted_rooms.lock_or_recover().insert(room_id);
+    }
     let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
     let is_ia_start = initial_state.whose_turn == Side::IASide;
-    room_to_gamestate.insert(
-        room_id,
-        GameState {
-            state: Phase::Start(initial_state),
-            config: cetkaik_full_state_transition::Config::cerke_online_alpha(),
-            waiting_for_after_half_acceptance: None,
-            moves_to_be_polled: [vec![], vec![], vec![], vec![]],
-            is_first_move_ia_move: is_first_turn_newtoken_turn,
-        },
+    let mut game_state = GameState::new(
+        initial_state,
+        cetkaik_full_state_transition::Config::cerke_online_alpha(),
     );
-
-    let game_state: &mut GameState = room_to_gamestate
-        .get_mut(&room_id)
-        .expect("FIXME: cannot happen");
-    game_state.set_first_mover(0_usize, is_ia_start, &mut rng);
+    let is_first_move_ia_move = game_state.set_first_mover(0_usize, is_ia_start, &mut rng);
+    room_to_gamestate.insert(room_id, game_state);
 
     RetVsCpuEntry::LetTheGameBegin {
-        access_token: format!("{}", new_token),
-        is_first_move_my_move: game_state.is_first_move_my_move(is_ia_down_for_newtoken, 0),
+        access_token: data.token_signer.issue(
+            new_token,
+            Some(&RoomInfoWithPerspective {
+                room_id,
+                is_ia_down_for_me: is_ia_down_for_newtoken,
+            }),
+        ),
+        is_first_move_my_move: is_first_move_ia_move.seen_by(is_ia_down_for_newtoken),
         is_ia_down_for_me: is_ia_down_for_newtoken,
     }
 }
@@ -206,27 +226,30 @@ pub fn random_entrance_cancel(
     msg: &web::Json<MsgWithAccessToken>,
     data: &web::Data<AppState>,
 ) -> RetRandomCancel {
-    if let Ok(access_token) = AccessToken::parse_str(&msg.access_token) {
-        let person_to_room = data.person_to_room.lock().unwrap();
-        let mut waiting_list = data.waiting_list.lock().unwrap();
-        match person_to_room.get(&access_token) {
-            // you already have a room. you cannot cancel
-            Some(_) => RetRandomCancel::Ok { cancellable: false },
-            None => {
-                if waiting_list.contains(&access_token) {
-                    // not yet assigned a room, but is in the waiting list
-                    waiting_list.remove(&access_token);
-                    RetRandomCancel::Ok { cancellable: true }
-                } else {
-                    // You told me to cancel, but I don't know you. Hmm...
-                    // well, at least you can cancel
-                    RetRandomCancel::Ok { cancellable: true }
+    match data.token_signer.verify(&msg.access_token) {
+        Ok(claims) => {
+            let access_token = claims.access_token();
+            let person_to_room = data.person_to_room.lock_or_recover();
+            let mut waiting_list = data.waiting_list.lock_or_recover();
+            match person_to_room.get(&access_token) {
+                // you already have a room. you cannot cancel
+                Some(_) => RetRandomCancel::Ok { cancellable: false },
+                None => {
+                    if waiting_list.contains(&access_token) {
+                        // not yet assigned a room, but is in the waiting list
+                        waiting_list.remove(&access_token);
+                        data.unlisted_on_entry
+                            .lock_or_recover()
+                            .remove(&access_token);
+                        RetRandomCancel::Ok { cancellable: true }
+                    } else {
+                        // You told me to cancel, but I don't know you. Hmm...
+                        // well, at least you can cancel
+                        RetRandomCancel::Ok { cancellable: true }
+                    }
                 }
             }
         }
-    } else {
-        RetRandomCancel::Err {
-            why_illegal: S("access token could not be parsed"),
-        }
+        Err(e) => RetRandomCancel::Err(e),
     }
 }
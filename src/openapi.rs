@@ -0,0 +1,412 @@
+//! The `OpenAPI` document served at `/openapi.json`, describing the `/v2/` endpoints.
+//! Request and response bodies are JSON Schemas derived from the very types that serde uses,
+//! so the schemas cannot drift from the wire format. The endpoints themselves are listed by hand in
+//! `endpoints`; a test next to the routes in `main.rs` checks that every one of them is served.
+
+use crate::types::{
+    AfterHalfAcceptanceMessageStruct, ChatContentStruct, MainMessage, MainMessageStruct,
+    MsgChatPoll, MsgRandomEntry, MsgVsCpuEntry, MsgWithAccessToken, MsgWithRoomId,
+    RetAfterHalfAcceptance, RetChatPoll, RetGameRecord, RetInfAfterStep, RetInfPoll, RetLegalMoves,
+    RetLiveRooms, RetMainPoll, RetNormalMove, RetRandomCancel, RetRandomEntry, RetRandomPoll,
+    RetRoomState, RetSendChat, RetSpectateEntry, RetSpectatorPoll, RetTaXot, RetTyMok,
+    RetUnlistRoom, RetValidate, RetVsCpuEntry, RetWhetherTyMokPoll,
+};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+};
+use serde_json::{json, Map, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Auth {
+    None,
+    /// The access token handed out on entry
+    Player,
+    /// The read-only token handed out by `/spectate/entry`
+    Spectator,
+}
+
+struct Endpoint {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    auth: Auth,
+    request: Option<Schema>,
+
+    /// Whether the request body may be left out
+    request_optional: bool,
+    response: Schema,
+
+    /// Whether `why_illegal` follows the `lang` parameter / `Accept-Language` header
+    localized: bool,
+}
+
+#[allow(clippy::too_many_lines)]
+fn endpoints(gen: &mut SchemaGenerator) -> Vec<Endpoint> {
+    let post = |path, summary, auth, request, response| Endpoint {
+        method: "post",
+        path,
+        summary,
+        auth,
+        request,
+        request_optional: false,
+        response,
+        localized: true,
+    };
+
+    vec![
+        post(
+            "/poll/main",
+            "Polls for the opponent's move",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetMainPoll>(),
+        ),
+        post(
+            "/poll/inf",
+            "Polls for the opponent's decision after a stepping ciurl",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetInfPoll>(),
+        ),
+        post(
+            "/poll/whethertymok",
+            "Polls for the opponent's ty mok / ta xot decision",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetWhetherTyMokPoll>(),
+        ),
+        post(
+            "/decision/tymok",
+            "Declares ty mok",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetTyMok>(),
+        ),
+        post(
+            "/decision/taxot",
+            "Declares ta xot",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetTaXot>(),
+        ),
+        post(
+            "/decision/main",
+            "Sends a move",
+            Auth::Player,
+            Some(gen.subschema_for::<MainMessage>()),
+            gen.subschema_for::<RetNormalMove>(),
+        ),
+        post(
+            "/decision/normalmove",
+            "Sends a move that needs no stepping ciurl",
+            Auth::Player,
+            Some(gen.subschema_for::<MainMessageStruct>()),
+            gen.subschema_for::<RetNormalMove>(),
+        ),
+        post(
+            "/decision/infafterstep",
+            "Sends a move that steps over a piece and then moves as the ciurl allows",
+            Auth::Player,
+            Some(gen.subschema_for::<MainMessageStruct>()),
+            gen.subschema_for::<RetInfAfterStep>(),
+        ),
+        post(
+            "/decision/afterhalfacceptance",
+            "Chooses the final destination after a stepping ciurl",
+            Auth::Player,
+            Some(gen.subschema_for::<AfterHalfAcceptanceMessageStruct>()),
+            gen.subschema_for::<RetAfterHalfAcceptance>(),
+        ),
+        post(
+            "/decision/validate",
+            "Checks whether a move would be accepted, without playing it",
+            Auth::Player,
+            Some(gen.subschema_for::<MainMessageStruct>()),
+            gen.subschema_for::<RetValidate>(),
+        ),
+        Endpoint {
+            localized: false,
+            request_optional: true,
+            ..post(
+                "/matching/random/entry",
+                "Enters random matching",
+                Auth::None,
+                Some(gen.subschema_for::<MsgRandomEntry>()),
+                gen.subschema_for::<RetRandomEntry>(),
+            )
+        },
+        Endpoint {
+            localized: false,
+            request_optional: true,
+            ..post(
+                "/matching/random/entry/staging",
+                "Enters random matching on staging",
+                Auth::None,
+                Some(gen.subschema_for::<MsgRandomEntry>()),
+                gen.subschema_for::<RetRandomEntry>(),
+            )
+        },
+        post(
+            "/matching/random/poll",
+            "Polls whether random matching has found an opponent",
+            Auth::None,
+            Some(gen.subschema_for::<MsgWithAccessToken>()),
+            gen.subschema_for::<RetRandomPoll>(),
+        ),
+        post(
+            "/matching/random/poll/staging",
+            "Polls whether random matching on staging has found an opponent",
+            Auth::None,
+            Some(gen.subschema_for::<MsgWithAccessToken>()),
+            gen.subschema_for::<RetRandomPoll>(),
+        ),
+        post(
+            "/matching/random/cancel",
+            "Leaves random matching",
+            Auth::None,
+            Some(gen.subschema_for::<MsgWithAccessToken>()),
+            gen.subschema_for::<RetRandomCancel>(),
+        ),
+        post(
+            "/matching/random/cancel/staging",
+            "Leaves random matching on staging",
+            Auth::None,
+            Some(gen.subschema_for::<MsgWithAccessToken>()),
+            gen.subschema_for::<RetRandomCancel>(),
+        ),
+        Endpoint {
+            localized: false,
+            request_optional: true,
+            ..post(
+                "/matching/vs_cpu/entry",
+                "Starts a game against the bot",
+                Auth::None,
+                Some(gen.subschema_for::<MsgVsCpuEntry>()),
+                gen.subschema_for::<RetVsCpuEntry>(),
+            )
+        },
+        Endpoint {
+            localized: false,
+            request_optional: true,
+            ..post(
+                "/matching/vs_cpu/entry/staging",
+                "Starts a game against the bot on staging",
+                Auth::None,
+                Some(gen.subschema_for::<MsgVsCpuEntry>()),
+                gen.subschema_for::<RetVsCpuEntry>(),
+            )
+        },
+        post(
+            "/spectate/entry",
+            "Obtains a read-only token for watching a room",
+            Auth::None,
+            Some(gen.subschema_for::<MsgWithRoomId>()),
+            gen.subschema_for::<RetSpectateEntry>(),
+        ),
+        post(
+            "/poll/spectate",
+            "Polls the moves made so far in a spectated room",
+            Auth::Spectator,
+            None,
+            gen.subschema_for::<RetSpectatorPoll>(),
+        ),
+        Endpoint {
+            method: "get",
+            localized: false,
+            ..post(
+                "/rooms/live",
+                "Lists ongoing rooms",
+                Auth::None,
+                None,
+                gen.subschema_for::<RetLiveRooms>(),
+            )
+        },
+        post(
+            "/room/unlist",
+            "Hides one's room from the live listing",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetUnlistRoom>(),
+        ),
+        post(
+            "/room/state",
+            "Returns the full position, for resynchronizing",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetRoomState>(),
+        ),
+        post(
+            "/room/legal_moves",
+            "Lists the moves available to the player to move",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetLegalMoves>(),
+        ),
+        post(
+            "/room/record",
+            "Exports the game record and chat log",
+            Auth::Player,
+            None,
+            gen.subschema_for::<RetGameRecord>(),
+        ),
+        post(
+            "/chat/send",
+            "Sends a chat message",
+            Auth::Player,
+            Some(gen.subschema_for::<ChatContentStruct>()),
+            gen.subschema_for::<RetSendChat>(),
+        ),
+        post(
+            "/poll/chat",
+            "Polls for chat messages",
+            Auth::Player,
+            Some(gen.subschema_for::<MsgChatPoll>()),
+            gen.subschema_for::<RetChatPoll>(),
+        ),
+    ]
+}
+
+fn operation(endpoint: Endpoint) -> Value {
+    let mut op = Map::new();
+    op.insert("summary".to_owned(), json!(endpoint.summary));
+    match endpoint.auth {
+        Auth::None => {}
+        Auth::Player => {
+            op.insert("security".to_owned(), json!([{ "accessToken": [] }]));
+        }
+        Auth::Spectator => {
+            op.insert("security".to_owned(), json!([{ "spectatorToken": [] }]));
+        }
+    }
+    if endpoint.localized {
+        op.insert(
+            "parameters".to_owned(),
+            json!([{ "$ref": "#/components/parameters/lang" }]),
+        );
+    }
+    if let Some(request) = endpoint.request {
+        op.insert(
+            "requestBody".to_owned(),
+            json!({
+                "required": !endpoint.request_optional,
+                "content": { "application/json": { "schema": request } }
+            }),
+        );
+    }
+    op.insert(
+        "responses".to_owned(),
+        json!({
+            "200": {
+                "description": "Errors are reported with status 200 as well, as a variant whose `type` is `Err`",
+                "content": { "application/json": { "schema": endpoint.response } }
+            },
+            "429": {
+                "description": "Rate limited; the `Retry-After` header and `retry_after_secs` tell how many seconds to wait",
+            }
+        }),
+    );
+    if endpoint.request_optional {
+        op["responses"]["400"] = json!({
+            "description": "The request body is there but cannot be read; `code` is `invalid_request`",
+        });
+    }
+    Value::Object(op)
+}
+
+#[must_use]
+pub fn document() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    let mut paths = Map::new();
+    for endpoint in endpoints(&mut gen) {
+        let (path, method) = (endpoint.path, endpoint.method);
+        let path_item = paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path_item[method] = operation(endpoint);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "cerke_online backend",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "The same endpoints are also served without the `/v2` prefix, but some of those keep the older response shapes for the sake of existing clients.",
+        },
+        "servers": [{ "url": "/v2" }],
+        "paths": paths,
+        "components": {
+            "schemas": gen.take_definitions(),
+            "securitySchemes": {
+                "accessToken": { "type": "http", "scheme": "bearer" },
+                "spectatorToken": { "type": "http", "scheme": "bearer" },
+            },
+            "parameters": {
+                "lang": {
+                    "name": "lang",
+                    "in": "query",
+                    "required": false,
+                    "description": "Language of human-readable messages (`en`, `ja` or `x-lineparine`); overrides `Accept-Language`",
+                    "schema": { "type": "string" },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document;
+    use serde_json::Value;
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match value {
+                        Value::String(target) if key == "$ref" => refs.push(target),
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_ref_resolves() {
+        let doc = document();
+        let mut refs = vec![];
+        collect_refs(&doc, &mut refs);
+        assert!(!refs.is_empty());
+        for target in refs {
+            let pointer = target.strip_prefix('#').unwrap();
+            assert!(doc.pointer(pointer).is_some(), "dangling $ref {target}");
+        }
+    }
+
+    #[test]
+    fn wire_names_are_kept() {
+        let doc = document();
+        let schemas = &doc["components"]["schemas"];
+        let schemas = serde_json::to_string(schemas).unwrap();
+        for name in [
+            "is_IA_down_for_me",
+            "plannedDirection",
+            "stepStyle",
+            "firstDest",
+            "LetTheGameBegin",
+            "why_illegal",
+            "not_your_turn",
+        ] {
+            assert!(
+                schemas.contains(name),
+                "`{name}` is missing from the schemas"
+            );
+        }
+        assert!(doc["paths"]["/rooms/live"]["get"].is_object());
+        assert!(doc["paths"]["/decision/main"]["post"]["requestBody"].is_object());
+    }
+}
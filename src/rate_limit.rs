@@ -0,0 +1,400 @@
+//! Per-IP and per-token rate limiting, applied to every route as middleware.
+//! Routes are grouped into entry, decision and poll routes, each with its own budget, so that
+//! a client polling every second is not starved by the same client spamming room creation.
+
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header;
+use actix_web::{Error, HttpResponse};
+use serde_json::json;
+
+use crate::types::{ApiError, ErrorCode, Locale, Localize, LockOrRecover};
+
+/// Once this many keys are tracked, the ones that have been idle for a whole window are dropped.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    /// Routes that allocate a room or a token
+    Entry,
+    /// Routes that change the state of a room
+    Decision,
+    /// Routes that only read
+    Poll,
+}
+
+impl RouteClass {
+    /// Classifies a path, with or without the `/v2` prefix. Paths outside the API are not limited.
+    #[must_use]
+    pub fn of(path: &str) -> Option<Self> {
+        let path = path.strip_prefix("/v2").unwrap_or(path);
+        if path.starts_with("/matching/random/poll") || path.starts_with("/poll/") {
+            Some(RouteClass::Poll)
+        } else if path.starts_with("/matching/") && path.contains("/entry")
+            || path == "/spectate/entry"
+        {
+            Some(RouteClass::Entry)
+        } else if path.starts_with("/matching/")
+            || path.starts_with("/decision/")
+            || path == "/chat/send"
+            || path == "/room/unlist"
+        {
+            Some(RouteClass::Decision)
+        } else if path.starts_with("/room/") || path.starts_with("/rooms/") {
+            Some(RouteClass::Poll)
+        } else {
+            None
+        }
+    }
+
+    fn env_name(self) -> &'static str {
+        match self {
+            RouteClass::Entry => "ENTRY",
+            RouteClass::Decision => "DECISION",
+            RouteClass::Poll => "POLL",
+        }
+    }
+}
+
+/// How many requests are allowed per window; `0` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub per_ip: usize,
+    pub per_token: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub window: Duration,
+    pub entry: Budget,
+    pub decision: Budget,
+    pub poll: Budget,
+
+    /// Whether to take the client's IP from the last `X-Forwarded-For` hop rather than from the socket,
+    /// which is what we want behind the Heroku router but lets anyone spoof their IP otherwise
+    pub trust_proxy: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_mins(1),
+            entry: Budget {
+                per_ip: 10,
+                per_token: 0,
+            },
+            decision: Budget {
+                per_ip: 300,
+                per_token: 120,
+            },
+            poll: Budget {
+                per_ip: 1200,
+                per_token: 600,
+            },
+            trust_proxy: false,
+        }
+    }
+}
+
+fn env_usize(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+impl RateLimitConfig {
+    /// Overrides the defaults with `RATE_LIMIT_WINDOW_SECS`, `RATE_LIMIT_TRUST_PROXY`
+    /// and `RATE_LIMIT_{ENTRY,DECISION,POLL}_PER_{IP,TOKEN}`.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = env_usize("RATE_LIMIT_WINDOW_SECS") {
+            config.window = Duration::from_secs(secs as u64);
+        }
+        config.trust_proxy = std::env::var("RATE_LIMIT_TRUST_PROXY")
+            .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+        for class in [RouteClass::Entry, RouteClass::Decision, RouteClass::Poll] {
+            let budget = config.budget_mut(class);
+            let name = class.env_name();
+            if let Some(n) = env_usize(&format!("RATE_LIMIT_{name}_PER_IP")) {
+                budget.per_ip = n;
+            }
+            if let Some(n) = env_usize(&format!("RATE_LIMIT_{name}_PER_TOKEN")) {
+                budget.per_token = n;
+            }
+        }
+        config
+    }
+
+    #[must_use]
+    pub fn budget(&self, class: RouteClass) -> Budget {
+        match class {
+            RouteClass::Entry => self.entry,
+            RouteClass::Decision => self.decision,
+            RouteClass::Poll => self.poll,
+        }
+    }
+
+    fn budget_mut(&mut self, class: RouteClass) -> &mut Budget {
+        match class {
+            RouteClass::Entry => &mut self.entry,
+            RouteClass::Decision => &mut self.decision,
+            RouteClass::Poll => &mut self.poll,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Token(String),
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    hits: Mutex<HashMap<(RouteClass, Client), Vec<Instant>>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn client_ip(&self, req: &ServiceRequest) -> Option<IpAddr> {
+        if self.config.trust_proxy {
+            let forwarded = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        req.peer_addr().map(|addr| addr.ip())
+    }
+
+    /// Records the request and returns `Err` with how long to wait if it is over budget.
+    /// Refused requests are not counted, so a client that backs off as told gets through.
+    pub fn check(&self, req: &ServiceRequest) -> Result<(), Duration> {
+        let Some(class) = RouteClass::of(req.path()) else {
+            return Ok(());
+        };
+        let budget = self.config.budget(class);
+        let mut clients = vec![];
+        if budget.per_ip > 0 {
+            if let Some(ip) = self.client_ip(req) {
+                clients.push((Client::Ip(ip), budget.per_ip));
+            }
+        }
+        if budget.per_token > 0 {
+            if let Some(token) = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+            {
+                clients.push((Client::Token(token.to_owned()), budget.per_token));
+            }
+        }
+        self.record(class, clients, Instant::now())
+    }
+
+    fn record(
+        &self,
+        class: RouteClass,
+        clients: Vec<(Client, usize)>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let window = self.config.window;
+        let mut hits = self.hits.lock_or_recover();
+        if hits.len() > SWEEP_THRESHOLD {
+            hits.retain(|_, log| {
+                log.last()
+                    .is_some_and(|last| now.duration_since(*last) < window)
+            });
+        }
+
+        let mut retry_after = None;
+        for (client, limit) in &clients {
+            let log = hits.entry((class, client.clone())).or_default();
+            log.retain(|at| now.duration_since(*at) < window);
+            if log.len() >= *limit {
+                let wait = window.saturating_sub(now.duration_since(log[log.len() - limit]));
+                retry_after = retry_after.max(Some(wait));
+            }
+        }
+        if let Some(wait) = retry_after {
+            return Err(wait);
+        }
+        for (client, _) in clients {
+            hits.entry((class, client)).or_default().push(now);
+        }
+        Ok(())
+    }
+}
+
+fn too_many_requests(retry_after: Duration, locale: Locale) -> HttpResponse {
+    // rounded up, so that retrying right on time never hits the limit again
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let error = ApiError::new(
+        ErrorCode::RateLimited,
+        format!("too many requests; retry after {secs} seconds"),
+    )
+    .localize(locale);
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, secs))
+        .json(json!({
+            "type": "Err",
+            "code": error.code,
+            "why_illegal": error.why_illegal,
+            "retry_after_secs": secs,
+        }))
+}
+
+/// The middleware; wrap the `App` with `RateLimit(limiter)`.
+pub struct RateLimit(pub Arc<RateLimiter>);
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.0.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        match self.limiter.check(&req) {
+            Ok(()) => {
+                let response = self.service.call(req);
+                Box::pin(async move { response.await.map(ServiceResponse::map_into_left_body) })
+            }
+            Err(retry_after) => {
+                let response = too_many_requests(retry_after, Locale::negotiate(req.request()));
+                Box::pin(ready(Ok(req.into_response(response).map_into_right_body())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{Budget, RateLimit, RateLimitConfig, RateLimiter, RouteClass};
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn route_classes() {
+        for (path, class) in [
+            ("/matching/vs_cpu/entry", Some(RouteClass::Entry)),
+            ("/v2/matching/random/entry/staging", Some(RouteClass::Entry)),
+            ("/spectate/entry", Some(RouteClass::Entry)),
+            ("/matching/random/poll", Some(RouteClass::Poll)),
+            ("/matching/random/cancel", Some(RouteClass::Decision)),
+            ("/v2/decision/main", Some(RouteClass::Decision)),
+            ("/room/unlist", Some(RouteClass::Decision)),
+            ("/room/state", Some(RouteClass::Poll)),
+            ("/v2/poll/main", Some(RouteClass::Poll)),
+            ("/openapi.json", None),
+            ("/", None),
+        ] {
+            assert_eq!(class, RouteClass::of(path), "{path}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn over_budget_requests_get_429() {
+        let limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+            window: Duration::from_mins(1),
+            poll: Budget {
+                per_ip: 0,
+                per_token: 2,
+            },
+            ..RateLimitConfig::default()
+        }));
+        let app = init_service(
+            App::new()
+                .wrap(RateLimit(limiter))
+                .route("/poll/main", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let poll = |token: &'static str| {
+            TestRequest::post()
+                .uri("/poll/main?lang=ja")
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            assert_eq!(
+                StatusCode::OK,
+                call_service(&app, poll("alice")).await.status()
+            );
+        }
+        let refused = call_service(&app, poll("alice")).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, refused.status());
+        let retry_after: u64 = refused
+            .headers()
+            .get(header::RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+        let body: serde_json::Value = read_body_json(refused).await;
+        assert_eq!("rate_limited", body["code"]);
+        assert_eq!("リクエストが多すぎます", body["why_illegal"]);
+        assert_eq!(retry_after, body["retry_after_secs"]);
+
+        // budgets are per token
+        assert_eq!(
+            StatusCode::OK,
+            call_service(&app, poll("bob")).await.status()
+        );
+    }
+}
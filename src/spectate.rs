@@ -0,0 +1,34 @@
+use crate::types::{
+    ApiError, AppState, ErrorCode, LockOrRecover, MsgWithRoomId, RetSpectateEntry, RoomId,
+    SpectatorToken,
+};
+use actix_web::web;
+use uuid::Uuid;
+
+#[must_use]
+pub fn spectate_entry_(
+    msg: &web::Json<MsgWithRoomId>,
+    data: &web::Data<AppState>,
+) -> RetSpectateEntry {
+    if let Ok(room_id) = RoomId::parse_str(&msg.room_id) {
+        let room_to_gamestate = data.room_to_gamestate.lock_or_recover();
+        // to spectators, an unlisted room looks as if it did not exist
+        let is_unlisted = data.unlisted_rooms.lock_or_recover().contains(&room_id);
+        if !room_to_gamestate.contains_key(&room_id) || is_unlisted {
+            return RetSpectateEntry::Err(ApiError::new(
+                ErrorCode::UnknownRoom,
+                format!("There is no room with id `{room_id}` to spectate"),
+            ));
+        }
+
+        let spectator_token = SpectatorToken(Uuid::new_v4());
+        let mut spectator_to_room = data.spectator_to_room.lock_or_recover();
+        spectator_to_room.insert(spectator_token, room_id);
+
+        RetSpectateEntry::Ok {
+            spectator_token: spectator_token.to_string(),
+        }
+    } else {
+        RetSpectateEntry::Err(ErrorCode::UnparsableRoomId.into())
+    }
+}
@@ -1,43 +1,128 @@
-
-
-
-use std::collections::{HashMap, HashSet};
-use std::{sync::Mutex};
 use cetkaik_full_state_transition::message::{AfterHalfAcceptance, InfAfterStep};
-use cetkaik_full_state_transition::state::HandResolved;
-
-use crate::types::{AfterHalfAcceptanceMessage, InfAfterStepInternal, MainMessage, NonTamMoveDotData, RetAfterHalfAcceptance, RetInfPoll, RetMainPoll, RetNormalMove, RetTaXot, RetTyMok, RetWhetherTyMokPoll, TamMoveInternal};
-
-use super::{AccessToken, GameState, Phase, RetInfAfterStep, RoomId, RoomInfoWithPerspective, WhoGoesFirst};
-
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::bot::thinking::{Thinking, Turn};
+use crate::bot::BotSettings;
+use crate::types::{
+    AfterHalfAcceptanceMessage, ChatContent, ChatMessage, InfAfterStepInternal, LiveRoom,
+    MainMessage, NonTamMoveDotData, RetAfterHalfAcceptance, RetChatPoll, RetGameRecord, RetInfPoll,
+    RetLegalMoves, RetLiveRooms, RetMainPoll, RetNormalMove, RetRoomState, RetSendChat,
+    RetSpectatorPoll, RetTaXot, RetTyMok, RetUnlistRoom, RetValidate, RetWhetherTyMokPoll,
+    TamMoveInternal, CHAT_RATE_LIMIT_COUNT, CHAT_RATE_LIMIT_WINDOW,
+};
+
+use super::bot::BotDifficulty;
+use super::token::REVOCATION_GRACE_PERIOD;
+use super::{
+    AccessToken, ApiError, ErrorCode, ExpectedPhase, GameState, LockOrRecover, Phase,
+    RetInfAfterStep, RoomId, RoomInfoWithPerspective, SpectatorToken, TokenSigner,
+};
+
+#[derive(Default)]
 pub struct AppState {
     pub access_counter: Mutex<i32>,
     pub waiting_list: Mutex<HashSet<AccessToken>>,
+
+    /// Those on the waiting list who have asked for their room to be unlisted
+    pub unlisted_on_entry: Mutex<HashSet<AccessToken>>,
     pub person_to_room: Mutex<HashMap<AccessToken, RoomInfoWithPerspective>>,
+    pub spectator_to_room: Mutex<HashMap<SpectatorToken, RoomId>>,
     pub rooms_where_opponent_is_bot: Mutex<HashSet<RoomId>>,
+    pub room_to_bot_difficulty: Mutex<HashMap<RoomId, BotDifficulty>>,
+    pub unlisted_rooms: Mutex<HashSet<RoomId>>,
+    pub room_to_chat: Mutex<HashMap<RoomId, Vec<ChatMessage>>>,
+    pub chat_rate_limit: Mutex<HashMap<(RoomId, bool), Vec<Instant>>>,
     pub room_to_gamestate: Mutex<HashMap<RoomId, GameState>>,
+    pub token_signer: TokenSigner,
+    pub bot_settings: BotSettings,
+
+    /// The bot of each room that is thinking about its move, or is done thinking and waits for the next main poll
+    pub bot_thinking: Mutex<HashMap<RoomId, Thinking>>,
+
+    /// When each finished game was first seen to be over; the tokens of these rooms are revoked
+    pub ended_games: Mutex<HashMap<RoomId, Instant>>,
 }
 
 impl AppState {
+    /// Runs `f` on the game of `room_info`, but only once `GameState::authorize` has let the sender
+    /// make a decision expected in the `expected` phase. Every state transition requested by a player goes through here.
+    fn decide<R>(
+        &self,
+        room_info: &RoomInfoWithPerspective,
+        expected: ExpectedPhase,
+        f: impl FnOnce(&mut GameState) -> R,
+    ) -> Result<R, ApiError> {
+        let mut room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let game_state: &mut GameState = room_to_gamestate
+            .get_mut(&room_info.room_id)
+            .ok_or_else(|| ApiError::from(ErrorCode::UnknownRoom))?;
+        game_state.authorize(room_info.is_ia_down_for_me, expected)?;
+        let ret = f(game_state);
+        if self.is_bot_s_turn_to_move(room_info, game_state) {
+            self.start_thinking(room_info.room_id, game_state);
+        }
+        Ok(ret)
+    }
+
+    /// Whether the opponent of `room_info` is a bot that is to make a move now.
+    /// The bot goes through the same check as a human sending a move would.
+    fn is_bot_s_turn_to_move(
+        &self,
+        room_info: &RoomInfoWithPerspective,
+        game_state: &GameState,
+    ) -> bool {
+        self.rooms_where_opponent_is_bot
+            .lock_or_recover()
+            .contains(&room_info.room_id)
+            && game_state
+                .authorize(!room_info.is_ia_down_for_me, ExpectedPhase::Start)
+                .is_ok()
+    }
+
+    /// Sets the bot of `room_id` thinking about its turn in `game_state`, unless it already is.
+    fn start_thinking(&self, room_id: RoomId, game_state: &GameState) {
+        let mut bot_thinking = self.bot_thinking.lock_or_recover();
+        if bot_thinking
+            .get(&room_id)
+            .is_some_and(|thinking| thinking.move_count == game_state.move_count())
+        {
+            return;
+        }
+        let difficulty = self.bot_difficulty_of(room_id);
+        let bot = crate::bot::bot_for(difficulty, &self.bot_settings);
+        let shown_after = self.bot_settings.think_time(difficulty).sample();
+        let thinking = Thinking::start(bot, game_state, shown_after);
+        bot_thinking.insert(room_id, thinking);
+    }
+
+    /// The bot of `room_id`'s turn in `game_state`, once it is done thinking; sets it thinking if it has not started yet.
+    fn think(&self, room_id: RoomId, game_state: &GameState) -> Option<Turn> {
+        self.start_thinking(room_id, game_state);
+        let mut bot_thinking = self.bot_thinking.lock_or_recover();
+        let result = bot_thinking
+            .get_mut(&room_id)?
+            .result(self.bot_settings.think_limit)?;
+        bot_thinking.remove(&room_id);
+        Some(result)
+    }
+
     pub fn analyze_afterhalfacceptance_message_and_update(
         &self,
         message: AfterHalfAcceptanceMessage,
         room_info: &RoomInfoWithPerspective,
     ) -> RetAfterHalfAcceptance {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-        println!("{:#?}", game_state.state.whose_turn());
-        println!("{:#?}", game_state.state.phase_name());
-
-        let res = match message {
-            AfterHalfAcceptanceMessage::AfterHalfAcceptance { dest } => {
-                game_state.apply_after_half_acceptance(AfterHalfAcceptance { dest })
-            },
-        };
-        game_state.apply_resolve();
-        res
+        self.decide(room_info, ExpectedPhase::AfterCiurl, |game_state| {
+            let res = match message {
+                AfterHalfAcceptanceMessage::AfterHalfAcceptance { dest } => {
+                    game_state.apply_after_half_acceptance(AfterHalfAcceptance { dest })
+                }
+            };
+            game_state.apply_resolve();
+            res
+        })
+        .unwrap_or_else(RetAfterHalfAcceptance::Err)
     }
 
     #[allow(clippy::too_many_lines)]
@@ -46,229 +131,188 @@ impl AppState {
         message: MainMessage,
         room_info: &RoomInfoWithPerspective,
     ) -> RetNormalMove {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-        println!("{:#?}", game_state.state.whose_turn());
-        println!("{:#?}", game_state.state.phase_name());
-
-        let res = match message {
-            MainMessage::TamMove {
-                flatten:
-                    TamMoveInternal::NoStep {
+        self.decide(room_info, ExpectedPhase::Start, |game_state| {
+            let res = match message {
+                MainMessage::TamMove {
+                    flatten:
+                        TamMoveInternal::NoStep {
+                            src,
+                            first_dest,
+                            second_dest,
+                        },
+                } => {
+                    let mov = cetkaik_full_state_transition::message::NormalMove::TamMoveNoStep {
                         src,
                         first_dest,
                         second_dest,
-                    },
-            } => {
-                let mov = cetkaik_full_state_transition::message::NormalMove::TamMoveNoStep {
-                    src,
-                    first_dest,
-                    second_dest,
-                }; 
-                game_state.apply_normal_move(mov)
-            }
+                    };
+                    game_state.apply_normal_move(mov)
+                }
+
+                MainMessage::TamMove {
+                    flatten:
+                        TamMoveInternal::StepsDuringFormer {
+                            src,
+                            step,
+                            first_dest,
+                            second_dest,
+                        },
+                } => {
+                    let mov =
+                        cetkaik_full_state_transition::message::NormalMove::TamMoveStepsDuringFormer {
+                            src,
+                            step,
+                            first_dest,
+                            second_dest,
+                        };
+                    game_state.apply_normal_move(mov)
+                }
 
-            MainMessage::TamMove {
-                flatten:
-                    TamMoveInternal::StepsDuringFormer {
+                MainMessage::TamMove {
+                    flatten:
+                        TamMoveInternal::StepsDuringLatter {
+                            src,
+                            step,
+                            first_dest,
+                            second_dest,
+                        },
+                } => {
+                    let mov =
+                        cetkaik_full_state_transition::message::NormalMove::TamMoveStepsDuringLatter {
+                            src,
+                            step,
+                            first_dest,
+                            second_dest,
+                        };
+                    game_state.apply_normal_move(mov)
+                },
+                MainMessage::NonTamMove {
+                    data: NonTamMoveDotData::FromHand {
+                        color,
+                        profession,
+                        dest,
+                    }
+                } => {
+                    let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveFromHopZuo {
+                        color: color.into(),
+                        prof: profession.into(),
+                        dest
+                    };
+                    game_state.apply_normal_move(mov)
+                },
+                MainMessage::NonTamMove {
+                    data: NonTamMoveDotData::SrcDst {
                         src,
-                        step,
-                        first_dest,
-                        second_dest,
-                    },
-            } => {
-                let mov =
-                    cetkaik_full_state_transition::message::NormalMove::TamMoveStepsDuringFormer {
+                        dest,
+                        water_entry_ciurl: _,
+                    }
+                } => {
+                    let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveSrcDst {
                         src,
-                        step,
-                        first_dest,
-                        second_dest,
-                    }; 
-                game_state.apply_normal_move(mov)
-            }
-
-            MainMessage::TamMove {
-                flatten:
-                    TamMoveInternal::StepsDuringLatter {
+                        dest
+                    };
+                    game_state.apply_normal_move(mov)
+                },
+                MainMessage::NonTamMove {
+                    data: NonTamMoveDotData::SrcStepDstFinite {
                         src,
                         step,
-                        first_dest,
-                        second_dest,
-                    },
-            } => {
-                let mov =
-                    cetkaik_full_state_transition::message::NormalMove::TamMoveStepsDuringLatter {
+                        dest,
+                        water_entry_ciurl: _
+                    }
+                } => {
+                    let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveSrcStepDstFinite {
                         src,
                         step,
-                        first_dest,
-                        second_dest,
-                    }; 
-                game_state.apply_normal_move(mov)
-            },
-            MainMessage::NonTamMove {
-                data: NonTamMoveDotData::FromHand {                    
-                    color,
-                    profession,
-                    dest,
-                }
-            } => {
-                let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveFromHopZuo {                
-                    color: color.into(),
-                    prof: profession.into(),
-                    dest                    
-                }; 
-                game_state.apply_normal_move(mov)
-            },
-            MainMessage::NonTamMove {
-                data: NonTamMoveDotData::SrcDst {
-                    src,
-                    dest,
-                    water_entry_ciurl,
-                }
-            } => {
-                let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveSrcDst {                
-                    src,
-                    dest
-                }; 
-                game_state.apply_normal_move(mov)
-            },
-            MainMessage::NonTamMove {
-                data: NonTamMoveDotData::SrcStepDstFinite {
-                    src,
-                    step,
-                    dest,
-                    water_entry_ciurl
-                }
-            } => {
-                let mov = cetkaik_full_state_transition::message::NormalMove::NonTamMoveSrcStepDstFinite {                
-                    src,
-                    step,
-                    dest
-                };
-                game_state.apply_normal_move(mov)
-            },
-            _ => todo!(),
-        };
-        game_state.apply_resolve();
-        res
+                        dest
+                    };
+                    game_state.apply_normal_move(mov)
+                },
+                MainMessage::InfAfterStep { .. } => {
+                    return RetNormalMove::Err(ApiError::new(
+                        ErrorCode::IllegalMoveKind,
+                        "InfAfterStep must be sent to decision/infafterstep",
+                    ));
+                },
+            };
+            game_state.apply_resolve();
+            res
+        })
+        .unwrap_or_else(RetNormalMove::Err)
     }
     pub fn analyze_inf_after_step_and_update(
         &self,
         message: MainMessage,
         room_info: &RoomInfoWithPerspective,
     ) -> RetInfAfterStep {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-
-        println!("{:#?}", game_state.state.whose_turn());
-        println!("{:#?}", game_state.state.phase_name());
-        
-        let res  = match message {
-            MainMessage::InfAfterStep {
-                flatten: InfAfterStepInternal { src, step, coord_signifying_planned_direction }
-            } => {
-
-                let mov = InfAfterStep {
-                    src,
-                    step,
-                    planned_direction: coord_signifying_planned_direction,
-                }; 
-                game_state.apply_inf_after_step(mov)
-            },
-            _ => todo!(),
-        };
-        game_state.apply_resolve();
-        res
+        self.decide(room_info, ExpectedPhase::Start, |game_state| {
+            let res = match message {
+                MainMessage::InfAfterStep {
+                    flatten:
+                        InfAfterStepInternal {
+                            src,
+                            step,
+                            coord_signifying_planned_direction,
+                        },
+                } => {
+                    let mov = InfAfterStep {
+                        src,
+                        step,
+                        planned_direction: coord_signifying_planned_direction,
+                    };
+                    game_state.apply_inf_after_step(mov)
+                }
+                MainMessage::NonTamMove { .. } | MainMessage::TamMove { .. } => {
+                    return RetInfAfterStep::Err(ApiError::new(
+                        ErrorCode::IllegalMoveKind,
+                        "only InfAfterStep can be sent to decision/infafterstep",
+                    ));
+                }
+            };
+            game_state.apply_resolve();
+            res
+        })
+        .unwrap_or_else(RetInfAfterStep::Err)
     }
 
     pub fn receive_tymok_and_update(&self, room_info: &RoomInfoWithPerspective) -> RetTyMok {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-        
-        let ia_side = room_info.is_ia_down_for_me;
-        if ia_side != game_state.is_ia_owner_s_turn() { 
-            return RetTyMok::Err;
-        }
-        
-        if let Phase::Moved(state) = &game_state.state {
-            let state_resolved = cetkaik_full_state_transition::resolve(state, game_state.config);
-            if let HandResolved::HandExists { if_taxot: _, if_tymok } = state_resolved {
-                game_state.state = Phase::Start(if_tymok);
-                RetTyMok::Ok
-            } else { 
-                RetTyMok::Err
-            }
-        } else {
-            RetTyMok::Err
-        }
+        self.decide(room_info, ExpectedPhase::Moved, GameState::apply_tymok)
+            .unwrap_or_else(RetTyMok::Err)
     }
 
     pub fn receive_taxot_and_update(&self, room_info: &RoomInfoWithPerspective) -> RetTaXot {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-
-        let ia_side = room_info.is_ia_down_for_me;
-        if ia_side != game_state.is_ia_owner_s_turn() { 
-            return RetTaXot::Err;
-        }
-
-        if let Phase::Moved(state) = &game_state.state {
-            let state_resolved = cetkaik_full_state_transition::resolve(state, game_state.config);
-            if let HandResolved::HandExists { if_taxot, if_tymok: _ }  = state_resolved {
-                game_state.state = match if_taxot {
-                    cetkaik_full_state_transition::IfTaxot::NextSeason(p_state) => {
-                        Phase::Start(p_state.choose().0)
-                    },
-                    cetkaik_full_state_transition::IfTaxot::VictoriousSide(_) => {
-                        return RetTaXot::Ok { 
-                            is_first_move_my_move: None
-                        }
-                    },
-                };
-                
-                let mut whos_go_first = WhoGoesFirst::new(&mut rand::thread_rng());
-                if whos_go_first.result != game_state.is_ia_owner_s_turn() {
-                    whos_go_first = whos_go_first.not();
-                }
-
-                RetTaXot::Ok { 
-                    is_first_move_my_move: Some(whos_go_first)
-                }
-            } else { 
-                RetTaXot::Err
-            }
-        } else {
-            RetTaXot::Err
-        }
+        self.decide(room_info, ExpectedPhase::Moved, GameState::apply_taxot)
+            .unwrap_or_else(RetTaXot::Err)
     }
 
     pub fn reply_to_whether_tymok_poll(
         &self,
         room_info: &RoomInfoWithPerspective,
     ) -> RetWhetherTyMokPoll {
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
+        let mut room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get_mut(&room_info.room_id) else {
+            return RetWhetherTyMokPoll::Err(ErrorCode::UnknownRoom.into());
+        };
 
-        let mov = game_state.get_last_move();
+        // a ta xot moves on to the next season, so the move that completed the hand may be in the previous one
+        let season = game_state.state.get_season().to_index();
+        let mov = game_state.moves_to_be_polled[..=season]
+            .iter()
+            .rev()
+            .find_map(|moves| moves.last());
         if let Some(mov) = mov {
             match mov.status {
                 Some(crate::types::HandCompletionStatus::TaXot) => RetWhetherTyMokPoll::TaXot {
-                    is_first_move_my_move: Some(
-                        game_state.is_first_move_my_move(room_info.is_ia_down_for_me, game_state.state.get_season().to_index())
-                    )
+                    is_first_move_my_move: if game_state.is_game_over() {
+                        None
+                    } else {
+                        game_state.is_first_move_my_move(room_info.is_ia_down_for_me, season)
+                    },
                 },
                 Some(crate::types::HandCompletionStatus::TyMok) => RetWhetherTyMokPoll::TyMok,
-                Some(crate::types::HandCompletionStatus::NotYetDetermined) | None => RetWhetherTyMokPoll::NotYetDetermined,
+                Some(crate::types::HandCompletionStatus::NotYetDetermined) | None => {
+                    RetWhetherTyMokPoll::NotYetDetermined
+                }
             }
         } else {
             RetWhetherTyMokPoll::NotYetDetermined
@@ -276,17 +320,11 @@ impl AppState {
     }
 
     pub fn reply_to_main_poll(&self, room_info: &RoomInfoWithPerspective) -> RetMainPoll {
-        use crate::bot::bot::BotMove;
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-
-        println!("{:#?}", game_state.state.whose_turn());
-        println!("{:#?}", game_state.state.phase_name());
+        let mut room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get_mut(&room_info.room_id) else {
+            return RetMainPoll::Err(ErrorCode::UnknownRoom.into());
+        };
 
-        let is_bot = self.rooms_where_opponent_is_bot.lock().unwrap().contains(&room_info.room_id);
-        
         let mov = game_state.get_last_move();
         // If the last move is not played by the player, just return what we have.
         if let Some(mov) = mov {
@@ -294,104 +332,279 @@ impl AppState {
                 return RetMainPoll::MoveMade {
                     content: mov.mov.clone(),
                     message: None,
-                }
+                    localized_message: None,
+                };
             }
         }
 
-        if is_bot {
-            match &game_state.state {
-                Phase::Start(_) => (),
-                Phase::BeforeCiurl(_) => (),
-                Phase::AfterCiurl(_) => (),
-                Phase::Moved(_) => (),
-            };
+        if !self.is_bot_s_turn_to_move(room_info, game_state) {
+            return RetMainPoll::NotYetDetermined;
+        }
+        // the bot plays its turn on a copy of the game, on a thread of its own, so that `room_to_gamestate` stays unlocked in the meantime
+        let Some((played, recorded)) = self.think(room_info.room_id, game_state) else {
+            return RetMainPoll::NotYetDetermined;
+        };
+        let (content, tactics) = match recorded {
+            Ok(recorded) => {
+                *game_state = played;
+                recorded
+            }
+            Err(e) => {
+                return RetMainPoll::Err(ApiError::new(
+                    ErrorCode::InternalError,
+                    format!("the bot failed to make a move: {}", e.why_illegal),
+                ));
+            }
+        };
+        // a ta xot may leave the bot to open the next season
+        if self.is_bot_s_turn_to_move(room_info, game_state) {
+            self.start_thinking(room_info.room_id, game_state);
+        }
 
-            if let Phase::Start(state) = &game_state.state {
-                println!("{:#?}", game_state.state.whose_turn());
-                let bot = crate::bot::bot_move(state, game_state.config);
+        RetMainPoll::MoveMade {
+            content,
+            message: tactics,
+            localized_message: None,
+        }
+    }
 
-                match bot.bot_move {
-                    BotMove::NormalMove(mov) => {
-                        game_state.apply_normal_move(mov);
-                    },
-                    BotMove::InfAfterStep { dat, after } => {
-                        if let RetInfAfterStep::Ok {ciurl} = game_state.apply_inf_after_step(dat) {
-                            let ret_after_half = game_state.apply_after_half_acceptance(after[ciurl.count()]);
-                            match ret_after_half {
-                                RetAfterHalfAcceptance::Err { why_illegal } => todo!(),
-                                RetAfterHalfAcceptance::WithWaterEntry { ciurl } => {},
-                                RetAfterHalfAcceptance::WithoutWaterEntry => {},
-                            }                            
-                        } else {
-                            todo!()
-                        }
-                    },
-                }
-                
-                match &game_state.state {
-                    Phase::Start(_) => todo!(),
-                    Phase::BeforeCiurl(_) => todo!(),
-                    Phase::AfterCiurl(_) => todo!(),
-                    Phase::Moved(state) => {
-                        if state.tam2tysak2_will_trigger_taxottymok {
-                            game_state.apply_taxot();
-                        } else {
-                            game_state.apply_resolve();
-                        }
-                    },
-                }
-                println!("{:#?}", game_state.state.whose_turn());
-
-                RetMainPoll::MoveMade {
-                    content: game_state.get_last_move().unwrap().mov.clone(),
-                    message: Some(bot.tactics),
-                } 
-            } else { 
-                todo!()
+    /// The difficulty of the bot sitting in `room_id`, as chosen on entering the room
+    fn bot_difficulty_of(&self, room_id: RoomId) -> BotDifficulty {
+        self.room_to_bot_difficulty
+            .lock_or_recover()
+            .get(&room_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn reply_to_spectator_poll(&self, room_id: RoomId) -> RetSpectatorPoll {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get(&room_id) else {
+            return RetSpectatorPoll::Err(ErrorCode::UnknownRoom.into());
+        };
+
+        RetSpectatorPoll::Ok {
+            current_season: game_state.state.get_season().to_index(),
+            seasons: game_state.spectated_seasons(),
+        }
+    }
+
+    pub fn receive_chat_and_update(
+        &self,
+        content: ChatContent,
+        room_info: &RoomInfoWithPerspective,
+    ) -> RetSendChat {
+        if let Err(e) = content.validate() {
+            return RetSendChat::Err(e);
+        }
+
+        let now = Instant::now();
+        let mut chat_rate_limit = self.chat_rate_limit.lock_or_recover();
+        let recently_sent = chat_rate_limit
+            .entry((room_info.room_id, room_info.is_ia_down_for_me))
+            .or_default();
+        recently_sent.retain(|sent_at| now.duration_since(*sent_at) < CHAT_RATE_LIMIT_WINDOW);
+        if recently_sent.len() >= CHAT_RATE_LIMIT_COUNT {
+            return RetSendChat::Err(ApiError::new(
+                ErrorCode::RateLimited,
+                format!(
+                    "too many chat messages; at most {CHAT_RATE_LIMIT_COUNT} messages are allowed per {} seconds",
+                    CHAT_RATE_LIMIT_WINDOW.as_secs()
+                ),
+            ));
+        }
+        recently_sent.push(now);
+
+        let mut room_to_chat = self.room_to_chat.lock_or_recover();
+        let chat = room_to_chat.entry(room_info.room_id).or_default();
+        let id = chat.len();
+        chat.push(ChatMessage {
+            id,
+            by_ia_owner: room_info.is_ia_down_for_me,
+            sent_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            content,
+        });
+        RetSendChat::Ok { id }
+    }
+
+    pub fn reply_to_chat_poll(
+        &self,
+        since: usize,
+        room_info: &RoomInfoWithPerspective,
+    ) -> RetChatPoll {
+        let room_to_chat = self.room_to_chat.lock_or_recover();
+        let messages = room_to_chat
+            .get(&room_info.room_id)
+            .map(|chat| chat.iter().skip(since).cloned().collect())
+            .unwrap_or_default();
+        RetChatPoll::Ok { messages }
+    }
+
+    pub fn export_game_record(&self, room_info: &RoomInfoWithPerspective) -> RetGameRecord {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get(&room_info.room_id) else {
+            return RetGameRecord::Err(ErrorCode::UnknownRoom.into());
+        };
+        let room_to_chat = self.room_to_chat.lock_or_recover();
+
+        RetGameRecord::Ok {
+            seasons: game_state.spectated_seasons(),
+            chat: room_to_chat
+                .get(&room_info.room_id)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn reply_to_room_state(&self, room_info: &RoomInfoWithPerspective) -> RetRoomState {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get(&room_info.room_id) else {
+            return RetRoomState::Err(ErrorCode::UnknownRoom.into());
+        };
+
+        RetRoomState::Ok {
+            state: game_state.room_state(room_info.is_ia_down_for_me),
+        }
+    }
+
+    pub fn validate_main_message(
+        &self,
+        message: MainMessage,
+        room_info: &RoomInfoWithPerspective,
+    ) -> RetValidate {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get(&room_info.room_id) else {
+            return RetValidate::Err(ErrorCode::UnknownRoom.into());
+        };
+
+        if room_info.is_ia_down_for_me != game_state.is_ia_owner_s_turn() {
+            return RetValidate::Illegal(ErrorCode::NotYourTurn.into());
+        }
+
+        game_state.validate(&message.into())
+    }
+
+    pub fn reply_to_legal_moves(&self, room_info: &RoomInfoWithPerspective) -> RetLegalMoves {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get(&room_info.room_id) else {
+            return RetLegalMoves::Err(ErrorCode::UnknownRoom.into());
+        };
+
+        if room_info.is_ia_down_for_me != game_state.is_ia_owner_s_turn() {
+            return RetLegalMoves::Err(ErrorCode::NotYourTurn.into());
+        }
+
+        if let Phase::Start(state) = &game_state.state {
+            let (hop1zuo1_candidates, candidates) = state.get_candidates(game_state.config);
+            RetLegalMoves::Ok {
+                hop1zuo1_candidates: hop1zuo1_candidates
+                    .into_iter()
+                    .map(MainMessage::from)
+                    .collect(),
+                candidates: candidates.into_iter().map(MainMessage::from).collect(),
             }
-            
         } else {
-            println!("nonbot");
-            RetMainPoll::NotYetDetermined
+            RetLegalMoves::Err(game_state.wrong_phase_error())
         }
     }
 
+    pub fn list_live_rooms(&self) -> RetLiveRooms {
+        let room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let rooms_where_opponent_is_bot = self.rooms_where_opponent_is_bot.lock_or_recover();
+        let unlisted_rooms = self.unlisted_rooms.lock_or_recover();
+
+        let rooms = room_to_gamestate
+            .iter()
+            .filter(|(room_id, game_state)| {
+                !unlisted_rooms.contains(room_id) && !game_state.is_game_over()
+            })
+            .map(|(room_id, game_state)| {
+                let scores = game_state.state.get_scores();
+                LiveRoom {
+                    room_id: *room_id,
+                    season: game_state.state.get_season().to_index(),
+                    ia_owner_s_score: scores.ia(),
+                    a_owner_s_score: scores.a(),
+                    opponent_is_bot: rooms_where_opponent_is_bot.contains(room_id),
+                    move_count: game_state.move_count(),
+                    elapsed_secs: game_state.started_at.elapsed().as_secs(),
+                }
+            })
+            .collect();
+
+        RetLiveRooms::Ok { rooms }
+    }
+
+    /// Refuses the tokens of a room once its game has been over for longer than `REVOCATION_GRACE_PERIOD`.
+    pub fn check_token_not_revoked(&self, room_id: RoomId) -> Result<(), ApiError> {
+        let is_game_over = self
+            .room_to_gamestate
+            .lock_or_recover()
+            .get(&room_id)
+            .is_some_and(GameState::is_game_over);
+        if !is_game_over {
+            return Ok(());
+        }
+        let mut ended_games = self.ended_games.lock_or_recover();
+        let ended_at = ended_games.entry(room_id).or_insert_with(Instant::now);
+        if ended_at.elapsed() > REVOCATION_GRACE_PERIOD {
+            Err(ErrorCode::RevokedToken.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Takes the room out of `/rooms/live`, and sends away the spectators already watching it.
+    pub fn unlist_room(&self, room_info: &RoomInfoWithPerspective) -> RetUnlistRoom {
+        self.unlisted_rooms
+            .lock_or_recover()
+            .insert(room_info.room_id);
+        self.spectator_to_room
+            .lock_or_recover()
+            .retain(|_, room_id| *room_id != room_info.room_id);
+        RetUnlistRoom::Ok
+    }
+
     pub fn reply_to_inf_poll(&self, room_info: &RoomInfoWithPerspective) -> RetInfPoll {
         use super::MoveToBePolled;
 
-        let mut room_to_gamestate = self.room_to_gamestate.lock().unwrap();
-        let game_state: &mut GameState = room_to_gamestate
-            .get_mut(&room_info.room_id)
-            .expect("FIXME: cannot happen");
-
+        let mut room_to_gamestate = self.room_to_gamestate.lock_or_recover();
+        let Some(game_state) = room_to_gamestate.get_mut(&room_info.room_id) else {
+            return RetInfPoll::Err(ErrorCode::UnknownRoom.into());
+        };
 
         let last_move = game_state.get_last_move();
-        
+
         if let Some(last_move) = last_move {
             if room_info.is_ia_down_for_me == last_move.by_ia_owner {
-                RetInfPoll::Err {
-                    why_illegal: "it's not your turn".to_string()
-                }
+                RetInfPoll::Err(ErrorCode::NotYourTurn.into())
             } else {
                 match &last_move.mov {
-                    MoveToBePolled::InfAfterStep { src, step, coord_signifying_planned_direction, stepping_ciurl, final_result } => {
-                        match final_result {
-                            Some(_) => RetInfPoll::MoveMade { content: last_move.mov.clone() },
-                            None => RetInfPoll::NotYetDetermined,
-                        }
+                    MoveToBePolled::InfAfterStep {
+                        src: _,
+                        step: _,
+                        coord_signifying_planned_direction: _,
+                        stepping_ciurl: _,
+                        final_result,
+                    } => match final_result {
+                        Some(_) => RetInfPoll::MoveMade {
+                            content: last_move.mov.clone(),
+                        },
+                        None => RetInfPoll::NotYetDetermined,
                     },
-                    _ => {
-                        RetInfPoll::Err {
-                            why_illegal: "InfAfterStep is not happening".to_string()
-                        }
-                    }
+                    _ => RetInfPoll::Err(ApiError::new(
+                        ErrorCode::WrongPhase,
+                        "InfAfterStep is not happening",
+                    )),
                 }
             }
         } else {
-            RetInfPoll::Err {
-                why_illegal: "there is no last move".to_string()
-            }
+            RetInfPoll::Err(ApiError::new(
+                ErrorCode::WrongPhase,
+                "there is no last move",
+            ))
         }
-
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,7 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct BotToken(pub Uuid);
 
@@ -15,11 +15,10 @@ impl BotToken {
 
 impl std::fmt::Display for BotToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.hyphenated().to_string(),)
+        write!(f, "{}", self.0.hyphenated())
     }
 }
 
-
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(into = "&'static str")]
 #[serde(try_from = "&str")]
@@ -41,7 +40,7 @@ impl TryFrom<&str> for TacticsKey {
             "avoid_defeat" => Ok(TacticsKey::AvoidDefeat),
             "loss_almost_certain" => Ok(TacticsKey::LossAlmostCertain),
             "neutral" => Ok(TacticsKey::Neutral),
-            s => Err(format!("unknown tactics name `{}` found. Please edit cerke_online_backend_rewritten repository.", s)),
+            s => Err(format!("unknown tactics name `{s}` found. Please edit cerke_online_backend_rewritten repository.")),
         }
     }
 
@@ -59,4 +58,22 @@ impl From<TacticsKey> for &'static str {
             TacticsKey::Neutral => "neutral",
         }
     }
-}
\ No newline at end of file
+}
+
+/// How hard the bot plays; chosen on entering a game against it, and kept for the whole game
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BotDifficulty {
+    /// Plays any legal move at random
+    Easy,
+
+    /// Looks one move ahead
+    #[default]
+    Normal,
+
+    /// Searches several moves ahead, within the server's `SearchBudget`
+    Hard,
+
+    /// Played by the external engine that the server is configured with, and as `Normal` if there is none
+    Engine,
+}
@@ -0,0 +1,164 @@
+use super::{ApiError, ErrorCode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Longest free-text chat message accepted, counted in `char`s.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 280;
+
+/// A single token may send at most `CHAT_RATE_LIMIT_COUNT` messages per `CHAT_RATE_LIMIT_WINDOW`.
+pub const CHAT_RATE_LIMIT_COUNT: usize = 5;
+pub const CHAT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ChatContent {
+    Text { text: String },
+    QuickPhrase { phrase: QuickPhrase },
+}
+
+impl ChatContent {
+    /// # Errors
+    /// Returns `Err` if the free text is empty or too long
+    pub fn validate(&self) -> Result<(), ApiError> {
+        match self {
+            ChatContent::Text { text } => {
+                if text.trim().is_empty() {
+                    Err(ApiError::new(
+                        ErrorCode::InvalidChatMessage,
+                        "chat message is empty",
+                    ))
+                } else if text.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+                    Err(ApiError::new(
+                        ErrorCode::InvalidChatMessage,
+                        format!("chat message is longer than {MAX_CHAT_MESSAGE_LENGTH} characters"),
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            ChatContent::QuickPhrase { .. } => Ok(()),
+        }
+    }
+}
+
+/// Fixed phrases that the frontend can render in the viewer's own language,
+/// so that players who don't share a language can still talk.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(into = "&'static str")]
+#[serde(try_from = "&str")]
+pub enum QuickPhrase {
+    Greetings,
+    GoodLuck,
+    GoodGame,
+    WellPlayed,
+    Thanks,
+    Sorry,
+    PleaseWait,
+    Oops,
+}
+
+impl TryFrom<&str> for QuickPhrase {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "greetings" => Ok(QuickPhrase::Greetings),
+            "good_luck" => Ok(QuickPhrase::GoodLuck),
+            "good_game" => Ok(QuickPhrase::GoodGame),
+            "well_played" => Ok(QuickPhrase::WellPlayed),
+            "thanks" => Ok(QuickPhrase::Thanks),
+            "sorry" => Ok(QuickPhrase::Sorry),
+            "please_wait" => Ok(QuickPhrase::PleaseWait),
+            "oops" => Ok(QuickPhrase::Oops),
+            s => Err(format!("unknown quick phrase `{s}` found")),
+        }
+    }
+
+    type Error = String;
+}
+
+impl From<QuickPhrase> for &'static str {
+    fn from(a: QuickPhrase) -> &'static str {
+        match a {
+            QuickPhrase::Greetings => "greetings",
+            QuickPhrase::GoodLuck => "good_luck",
+            QuickPhrase::GoodGame => "good_game",
+            QuickPhrase::WellPlayed => "well_played",
+            QuickPhrase::Thanks => "thanks",
+            QuickPhrase::Sorry => "sorry",
+            QuickPhrase::PleaseWait => "please_wait",
+            QuickPhrase::Oops => "oops",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct ChatMessage {
+    /// Position of this message in the room's chat log; pass it back as `since` when polling.
+    pub id: usize,
+
+    #[serde(rename = "by_IA_owner")]
+    pub by_ia_owner: bool,
+
+    /// Seconds since the UNIX epoch
+    pub sent_at: u64,
+    pub content: ChatContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+pub struct ChatContentStruct {
+    pub message: ChatContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub struct MsgChatPoll {
+    pub since: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetSendChat {
+    Ok { id: usize },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetChatPoll {
+    Ok { messages: Vec<ChatMessage> },
+    Err(ApiError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChatContent, ChatContentStruct, QuickPhrase, MAX_CHAT_MESSAGE_LENGTH};
+    use big_s::S;
+
+    #[test]
+    fn quick_phrase_serde() {
+        let json_str = r#"{"message":{"type":"QuickPhrase","phrase":"good_game"}}"#;
+        let result: ChatContentStruct = serde_json::from_str(json_str).unwrap();
+        assert_eq!(
+            result.message,
+            ChatContent::QuickPhrase {
+                phrase: QuickPhrase::GoodGame
+            }
+        );
+        assert_eq!(json_str, serde_json::to_string(&result).unwrap());
+    }
+
+    #[test]
+    fn text_length_limit() {
+        let ok = ChatContent::Text {
+            text: "あ".repeat(MAX_CHAT_MESSAGE_LENGTH),
+        };
+        assert!(ok.validate().is_ok());
+
+        let too_long = ChatContent::Text {
+            text: "a".repeat(MAX_CHAT_MESSAGE_LENGTH + 1),
+        };
+        assert!(too_long.validate().is_err());
+
+        let blank = ChatContent::Text { text: S(" \n") };
+        assert!(blank.validate().is_err());
+    }
+}
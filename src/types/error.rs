@@ -0,0 +1,97 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Stable, machine-readable reason why a request was refused.
+/// The frontend should branch on this rather than on `why_illegal`, which is meant for humans.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    UnparsableToken,
+    UnknownToken,
+    ReadOnlyToken,
+    InvalidToken,
+    ExpiredToken,
+    RevokedToken,
+    UnparsableRoomId,
+    UnknownRoom,
+    NotYourTurn,
+    WrongPhase,
+    IllegalMove,
+    IllegalMoveKind,
+    NoHandToResolve,
+    GameOver,
+    InvalidChatMessage,
+    RateLimited,
+    InvalidRequest,
+    InternalError,
+}
+
+impl ErrorCode {
+    #[must_use]
+    pub fn default_message(self) -> &'static str {
+        match self {
+            ErrorCode::UnparsableToken => "access token could not be parsed",
+            ErrorCode::UnknownToken => "unrecognized access token",
+            ErrorCode::ReadOnlyToken => "this token is read-only and cannot be used here",
+            ErrorCode::InvalidToken => "the access token was not issued by this server",
+            ErrorCode::ExpiredToken => "the access token has expired",
+            ErrorCode::RevokedToken => "the game is over, so the access token is no longer valid",
+            ErrorCode::UnparsableRoomId => "room id could not be parsed",
+            ErrorCode::UnknownRoom => "there is no such room",
+            ErrorCode::NotYourTurn => "it's not your turn",
+            ErrorCode::WrongPhase => "Invalid State",
+            ErrorCode::IllegalMove => "the move is illegal",
+            ErrorCode::IllegalMoveKind => "this kind of move cannot be sent to this endpoint",
+            ErrorCode::NoHandToResolve => "there is no hand to decide ty mok or ta xot on",
+            ErrorCode::GameOver => "the game is already over",
+            ErrorCode::InvalidChatMessage => "invalid chat message",
+            ErrorCode::RateLimited => "too many requests",
+            ErrorCode::InvalidRequest => "the request body could not be read",
+            ErrorCode::InternalError => "something went wrong on the server",
+        }
+    }
+}
+
+/// The payload of every `Err` variant sent to the client.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub why_illegal: String,
+}
+
+impl ApiError {
+    #[must_use]
+    pub fn new(code: ErrorCode, why_illegal: impl Into<String>) -> Self {
+        Self {
+            code,
+            why_illegal: why_illegal.into(),
+        }
+    }
+}
+
+impl From<ErrorCode> for ApiError {
+    fn from(code: ErrorCode) -> Self {
+        Self::new(code, code.default_message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiError, ErrorCode};
+    use crate::types::{RetNormalMove, RetTyMok};
+
+    #[test]
+    fn error_serde() {
+        let ret = RetNormalMove::Err(ApiError::from(ErrorCode::NotYourTurn));
+        let json_str =
+            r#"{"type":"Err","code":"not_your_turn","why_illegal":"it's not your turn"}"#;
+        assert_eq!(json_str, serde_json::to_string(&ret).unwrap());
+        assert_eq!(ret, serde_json::from_str(json_str).unwrap());
+
+        let ret = RetTyMok::Err(ApiError::from(ErrorCode::NoHandToResolve));
+        assert_eq!(
+            r#"{"type":"Err","code":"no_hand_to_resolve","why_illegal":"there is no hand to decide ty mok or ta xot on"}"#,
+            serde_json::to_string(&ret).unwrap()
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use cetkaik_full_state_transition::{state, Season};
+use cetkaik_full_state_transition::{state, Rate, Scores, Season};
 use rand::{prelude::ThreadRng, Rng};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
@@ -8,7 +9,7 @@ use super::MoveToBePolled;
 
 pub type AbsoluteCoord = cetkaik_core::absolute::Coord;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Phase {
     Start(state::GroundState),
     BeforeCiurl(state::ExcitedStateWithoutCiurl),
@@ -46,6 +47,68 @@ impl Phase {
             Phase::Moved(x) => x.season,
         }
     }
+
+    #[must_use]
+    pub fn get_field(&self) -> &cetkaik_core::absolute::Field {
+        match self {
+            Phase::Start(x) => &x.f,
+            Phase::BeforeCiurl(x) => &x.f,
+            Phase::AfterCiurl(x) => &x.c.f,
+            Phase::Moved(x) => &x.f,
+        }
+    }
+
+    #[must_use]
+    pub fn get_rate(&self) -> Rate {
+        match self {
+            Phase::Start(x) => x.rate,
+            Phase::BeforeCiurl(x) => x.rate,
+            Phase::AfterCiurl(x) => x.c.rate,
+            Phase::Moved(x) => x.rate,
+        }
+    }
+
+    #[must_use]
+    pub fn get_scores(&self) -> Scores {
+        match self {
+            Phase::Start(x) => x.scores,
+            Phase::BeforeCiurl(x) => x.scores,
+            Phase::AfterCiurl(x) => x.c.scores,
+            Phase::Moved(x) => x.scores,
+        }
+    }
+}
+
+/// The phase in which a kind of decision can be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedPhase {
+    /// A move, either a normal move or the first half of an `InfAfterStep`
+    Start,
+    /// The second half of an `InfAfterStep`, once the stepping ciurl has been cast
+    AfterCiurl,
+    /// Ty mok or ta xot, once a hand has been completed
+    Moved,
+}
+
+impl ExpectedPhase {
+    #[must_use]
+    pub fn matches(self, phase: &Phase) -> bool {
+        matches!(
+            (self, phase),
+            (ExpectedPhase::Start, Phase::Start(_))
+                | (ExpectedPhase::AfterCiurl, Phase::AfterCiurl(_))
+                | (ExpectedPhase::Moved, Phase::Moved(_))
+        )
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            ExpectedPhase::Start => "Start",
+            ExpectedPhase::AfterCiurl => "AfterCiurl",
+            ExpectedPhase::Moved => "Moved",
+        }
+    }
 }
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub struct SrcStep {
@@ -59,7 +122,7 @@ pub struct MovePiece {
     pub status: Option<HandCompletionStatus>,
     pub by_ia_owner: bool,
 }
-#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone, JsonSchema)]
 pub enum HandCompletionStatus {
     TyMok,
     TaXot,
@@ -139,7 +202,61 @@ impl From<cetkaik_core::Profession> for Profession {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+/// A piece on the board, as seen from one of the players.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, JsonSchema)]
+#[serde(tag = "type")]
+pub enum PieceWithPerspective {
+    Tam2,
+    NonTam2Piece {
+        color: Color,
+        profession: Profession,
+        is_mine: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, JsonSchema)]
+pub struct PieceOnBoard {
+    #[serde(with = "serde_coord")]
+    #[schemars(with = "serde_coord::CoordSchema")]
+    pub coord: AbsoluteCoord,
+    pub piece: PieceWithPerspective,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, JsonSchema)]
+pub struct PieceInHand {
+    pub color: Color,
+    pub profession: Profession,
+}
+
+impl From<cetkaik_core::absolute::NonTam2Piece> for PieceInHand {
+    fn from(piece: cetkaik_core::absolute::NonTam2Piece) -> Self {
+        Self {
+            color: piece.color.into(),
+            profession: piece.prof.into(),
+        }
+    }
+}
+
+/// Everything a client needs to redraw the game from scratch, e.g. after a reload.
+/// Coordinates stay absolute; `is_ia_down_for_me` tells the client how to orient them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, JsonSchema)]
+pub struct RoomState {
+    #[serde(rename = "is_IA_down_for_me")]
+    pub is_ia_down_for_me: bool,
+    pub board: Vec<PieceOnBoard>,
+    pub my_hop1zuo1: Vec<PieceInHand>,
+    pub opponent_hop1zuo1: Vec<PieceInHand>,
+    pub is_my_turn: bool,
+    pub season: usize,
+    pub my_score: i32,
+    pub opponent_score: i32,
+    pub rate: i32,
+
+    /// Present while the stepping ciurl has been cast but the destination is not yet decided
+    pub pending_inf_after_step: Option<MoveToBePolled>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, JsonSchema)]
 // Using boolean is natural, and this is also necessary to allow easy interop with the frontend
 #[allow(clippy::struct_excessive_bools)]
 pub struct Ciurl(bool, bool, bool, bool, bool);
@@ -152,7 +269,11 @@ impl Ciurl {
 
     #[must_use]
     pub fn count(self) -> usize {
-        self.0 as usize + self.1 as usize + self.2 as usize + self.3 as usize + self.4 as usize
+        usize::from(self.0)
+            + usize::from(self.1)
+            + usize::from(self.2)
+            + usize::from(self.3)
+            + usize::from(self.4)
     }
 }
 
@@ -170,7 +291,7 @@ impl From<usize> for Ciurl {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(tag = "type")]
 pub enum NormalMove {
     NonTamMove {
@@ -316,78 +437,94 @@ impl From<NormalMove> for cetkaik_full_state_transition::message::NormalMove {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, JsonSchema)]
 #[serde(tag = "type")]
-
 pub enum NonTamMoveDotData {
     FromHand {
         color: Color,
         profession: Profession,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         dest: AbsoluteCoord,
     },
     SrcDst {
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         dest: AbsoluteCoord,
         #[serde(skip_serializing_if = "Option::is_none")]
         water_entry_ciurl: Option<Ciurl>,
     },
     SrcStepDstFinite {
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         step: AbsoluteCoord,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         dest: AbsoluteCoord,
         #[serde(skip_serializing_if = "Option::is_none")]
         water_entry_ciurl: Option<Ciurl>,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, JsonSchema)]
 #[serde(tag = "stepStyle")]
 pub enum TamMoveInternal {
     NoStep {
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "firstDest")]
         first_dest: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "secondDest")]
         second_dest: AbsoluteCoord,
     },
 
     StepsDuringFormer {
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         step: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "firstDest")]
         first_dest: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "secondDest")]
         second_dest: AbsoluteCoord,
     },
 
     StepsDuringLatter {
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         step: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "firstDest")]
         first_dest: AbsoluteCoord,
 
         #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "secondDest")]
         second_dest: AbsoluteCoord,
     },
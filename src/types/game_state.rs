@@ -1,16 +1,20 @@
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use cetkaik_full_state_transition::{
     message::{AfterHalfAcceptance, InfAfterStep, NormalMove, PureMove},
-    state::HandResolved,
+    probabilistic::Probabilistic,
+    state::{GroundState, HandResolved},
     Config,
 };
 
-use crate::types::FinalResult;
+use crate::types::{FinalResult, HandCompletionStatus, LockOrRecover};
 
 use super::{
-    Ciurl, MovePiece, MoveToBePolled, NonTamMoveDotData, Phase, RetAfterHalfAcceptance,
-    RetInfAfterStep, RetNormalMove, RetTaXot, SrcStep, TamMoveInternal, WhoGoesFirst,
+    ApiError, Ciurl, ErrorCode, ExpectedPhase, MovePiece, MoveToBePolled, NonTamMoveDotData, Phase,
+    PieceInHand, PieceOnBoard, PieceWithPerspective, RetAfterHalfAcceptance, RetInfAfterStep,
+    RetNormalMove, RetTaXot, RetTyMok, RetValidate, RoomState, SpectatedMove, SpectatedSeason,
+    SrcStep, TamMoveInternal, WhoGoesFirst,
 };
 
 #[derive(Debug)]
@@ -20,14 +24,191 @@ pub struct GameState {
     pub waiting_for_after_half_acceptance: Option<SrcStep>,
     pub moves_to_be_polled: [Vec<MovePiece>; 4],
     pub is_first_move_ia_move: Arc<Mutex<[Option<WhoGoesFirst>; 4]>>,
+    pub started_at: Instant,
 }
 
 impl GameState {
+    /// A game in which no move has been made yet, and who goes first in each season is yet to be decided
+    #[must_use]
+    pub fn new(initial_state: GroundState, config: Config) -> Self {
+        Self {
+            state: Phase::Start(initial_state),
+            config,
+            waiting_for_after_half_acceptance: None,
+            moves_to_be_polled: [vec![], vec![], vec![], vec![]],
+            is_first_move_ia_move: Arc::new(Mutex::new([None, None, None, None])),
+            started_at: Instant::now(),
+        }
+    }
+
     #[must_use]
     pub fn is_ia_owner_s_turn(&self) -> bool {
         self.state.whose_turn() == cetkaik_core::absolute::Side::IASide
     }
 
+    /// The error to report when a request does not fit the current phase;
+    /// once the game is over, every phase is the wrong one.
+    #[must_use]
+    pub fn wrong_phase_error(&self) -> ApiError {
+        if self.is_game_over() {
+            ErrorCode::GameOver.into()
+        } else {
+            ErrorCode::WrongPhase.into()
+        }
+    }
+
+    /// The check that every decision goes through before it touches the state:
+    /// the game must still be going on, it must be the sender's turn,
+    /// and the game must be waiting for that kind of decision.
+    pub fn authorize(
+        &self,
+        is_ia_down_for_me: bool,
+        expected: ExpectedPhase,
+    ) -> Result<(), ApiError> {
+        if self.is_game_over() {
+            return Err(ErrorCode::GameOver.into());
+        }
+        if is_ia_down_for_me != self.is_ia_owner_s_turn() {
+            return Err(ErrorCode::NotYourTurn.into());
+        }
+        if !expected.matches(&self.state) {
+            return Err(ApiError::new(
+                ErrorCode::WrongPhase,
+                format!(
+                    "this decision can only be made in the {} phase, but the game is in the {} phase",
+                    expected.name(),
+                    self.state.phase_name()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the game has been decided, either by a ta xot that ended the last season
+    /// or by a player running out of points.
+    #[must_use]
+    pub fn is_game_over(&self) -> bool {
+        if let Phase::Moved(state) = &self.state {
+            match cetkaik_full_state_transition::resolve(state, self.config) {
+                HandResolved::GameEndsWithoutTymokTaxot(_) => true,
+                HandResolved::HandExists {
+                    if_taxot: cetkaik_full_state_transition::IfTaxot::VictoriousSide(_),
+                    if_tymok: _,
+                } => self
+                    .get_last_move()
+                    .is_some_and(|mov| mov.status == Some(HandCompletionStatus::TaXot)),
+                HandResolved::HandExists { .. } | HandResolved::NeitherTymokNorTaxot(_) => false,
+            }
+        } else {
+            false
+        }
+    }
+
+    /// Every move made so far, grouped by season, up to and including the current one.
+    #[must_use]
+    pub fn spectated_seasons(&self) -> Vec<SpectatedSeason> {
+        let current_season = self.state.get_season().to_index();
+        let is_first_move_ia_move = self.is_first_move_ia_move.lock_or_recover();
+        self.moves_to_be_polled[..=current_season]
+            .iter()
+            .zip(is_first_move_ia_move.iter())
+            .map(|(moves, is_first_move_ia_move)| SpectatedSeason {
+                is_first_move_ia_move: is_first_move_ia_move.clone(),
+                moves: moves
+                    .iter()
+                    .map(|mov| SpectatedMove {
+                        by_ia_owner: mov.by_ia_owner,
+                        content: mov.mov.clone(),
+                        status: mov.status,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn room_state(&self, is_ia_down_for_me: bool) -> RoomState {
+        use cetkaik_core::absolute::{Piece, Side};
+        let my_side = if is_ia_down_for_me {
+            Side::IASide
+        } else {
+            Side::ASide
+        };
+        let field = self.state.get_field();
+        let board = field
+            .board
+            .iter()
+            .map(|(coord, piece)| PieceOnBoard {
+                coord: *coord,
+                piece: match *piece {
+                    Piece::Tam2 => PieceWithPerspective::Tam2,
+                    Piece::NonTam2Piece { color, prof, side } => {
+                        PieceWithPerspective::NonTam2Piece {
+                            color: color.into(),
+                            profession: prof.into(),
+                            is_mine: side == my_side,
+                        }
+                    }
+                },
+            })
+            .collect();
+        let ia_side_hop1zuo1 = field
+            .ia_side_hop1zuo1
+            .iter()
+            .copied()
+            .map(PieceInHand::from);
+        let a_side_hop1zuo1 = field.a_side_hop1zuo1.iter().copied().map(PieceInHand::from);
+        let (my_hop1zuo1, opponent_hop1zuo1) = if is_ia_down_for_me {
+            (ia_side_hop1zuo1.collect(), a_side_hop1zuo1.collect())
+        } else {
+            (a_side_hop1zuo1.collect(), ia_side_hop1zuo1.collect())
+        };
+        let scores = self.state.get_scores();
+        let (my_score, opponent_score) = if is_ia_down_for_me {
+            (scores.ia(), scores.a())
+        } else {
+            (scores.a(), scores.ia())
+        };
+        let pending_inf_after_step = match (&self.state, self.get_last_move()) {
+            (Phase::AfterCiurl(_), Some(last_move)) => Some(last_move.mov.clone()),
+            _ => None,
+        };
+
+        RoomState {
+            is_ia_down_for_me,
+            board,
+            my_hop1zuo1,
+            opponent_hop1zuo1,
+            is_my_turn: self.state.whose_turn() == my_side,
+            season: self.state.get_season().to_index(),
+            my_score,
+            opponent_score,
+            rate: self.state.get_rate().num(),
+            pending_inf_after_step,
+        }
+    }
+
+    /// A copy of the game that shares nothing with it, so that a bot can play its turn on the copy
+    /// without holding the lock of the rooms.
+    #[must_use]
+    pub fn detached(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            config: self.config,
+            waiting_for_after_half_acceptance: self.waiting_for_after_half_acceptance,
+            moves_to_be_polled: self.moves_to_be_polled.clone(),
+            is_first_move_ia_move: Arc::new(Mutex::new(
+                self.is_first_move_ia_move.lock_or_recover().clone(),
+            )),
+            started_at: self.started_at,
+        }
+    }
+
+    #[must_use]
+    pub fn move_count(&self) -> usize {
+        self.moves_to_be_polled.iter().map(Vec::len).sum()
+    }
+
     #[must_use]
     pub fn get_last_move(&self) -> Option<&MovePiece> {
         self.moves_to_be_polled[self.state.get_season() as usize].last()
@@ -41,6 +222,14 @@ impl GameState {
         self.moves_to_be_polled[self.state.get_season() as usize].push(move_piece);
     }
 
+    /// Records the ty mok / ta xot decision on the move that completed the hand.
+    /// Must be called before the state transitions into the next season.
+    pub fn set_last_move_status(&mut self, status: HandCompletionStatus) {
+        if let Some(last_move) = self.get_last_move_mut() {
+            last_move.status = Some(status);
+        }
+    }
+
     #[allow(clippy::too_many_lines)]
     pub fn apply_normal_move(&mut self, mov: NormalMove) -> RetNormalMove {
         if let Phase::Start(state) = &self.state {
@@ -77,9 +266,7 @@ impl GameState {
                                 RetNormalMove::WithoutWaterEntry
                             }
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
                 NormalMove::NonTamMoveSrcStepDstFinite { src, step, dest } => {
@@ -115,9 +302,7 @@ impl GameState {
                                 RetNormalMove::WithoutWaterEntry
                             }
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
                 NormalMove::NonTamMoveFromHopZuo { color, prof, dest } => {
@@ -138,9 +323,7 @@ impl GameState {
                             self.state = Phase::Moved(next_state_p.choose_when_no_ciurl());
                             RetNormalMove::WithoutWaterEntry
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
                 NormalMove::TamMoveNoStep {
@@ -165,9 +348,7 @@ impl GameState {
                             self.state = Phase::Moved(next_state_p.choose_when_no_ciurl());
                             RetNormalMove::WithoutWaterEntry
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
                 NormalMove::TamMoveStepsDuringFormer {
@@ -195,9 +376,7 @@ impl GameState {
                             self.state = Phase::Moved(next_state_p.choose_when_no_ciurl());
                             RetNormalMove::WithoutWaterEntry
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
                 NormalMove::TamMoveStepsDuringLatter {
@@ -225,16 +404,12 @@ impl GameState {
                             self.state = Phase::Moved(next_state_p.choose_when_no_ciurl());
                             RetNormalMove::WithoutWaterEntry
                         }
-                        Err(e) => RetNormalMove::Err {
-                            why_illegal: e.to_string(),
-                        },
+                        Err(e) => RetNormalMove::Err(ApiError::new(ErrorCode::IllegalMove, e)),
                     }
                 }
             }
         } else {
-            RetNormalMove::Err {
-                why_illegal: "Invalid State".to_string(),
-            }
+            RetNormalMove::Err(self.wrong_phase_error())
         }
     }
 
@@ -250,7 +425,12 @@ impl GameState {
             match next_state {
                 Ok(next_state_p) => {
                     let (next_state, ciurl_value) = next_state_p.choose();
-                    let ciurl_value = ciurl_value.unwrap();
+                    let Some(ciurl_value) = ciurl_value else {
+                        return RetInfAfterStep::Err(ApiError::new(
+                            ErrorCode::InternalError,
+                            "no stepping ciurl was cast",
+                        ));
+                    };
                     let ciurl = Ciurl::from(ciurl_value);
                     let move_to_be_polled = MoveToBePolled::InfAfterStep {
                         src,
@@ -267,24 +447,53 @@ impl GameState {
                     self.state = Phase::AfterCiurl(next_state);
                     RetInfAfterStep::Ok { ciurl }
                 }
-                Err(e) => RetInfAfterStep::Err {
-                    why_illegal: e.to_string(),
-                },
+                Err(e) => RetInfAfterStep::Err(ApiError::new(ErrorCode::IllegalMove, e)),
             }
         } else {
-            RetInfAfterStep::Err {
-                why_illegal: "Invalid State".to_string(),
-            }
+            RetInfAfterStep::Err(self.wrong_phase_error())
         }
     }
 
-    pub fn apply_pure_move(&mut self, mov: PureMove) {
+    pub fn apply_pure_move(&mut self, mov: &PureMove) {
         match mov {
             PureMove::InfAfterStep(mov) => {
-                self.apply_inf_after_step(mov);
+                self.apply_inf_after_step(*mov);
             }
             PureMove::NormalMove(mov) => {
-                self.apply_normal_move(mov);
+                self.apply_normal_move(*mov);
+            }
+        }
+    }
+
+    /// Tells whether `mov` would be accepted in the current state, without modifying the state
+    /// nor casting any sticks.
+    #[must_use]
+    pub fn validate(&self, mov: &PureMove) -> RetValidate {
+        let Phase::Start(state) = &self.state else {
+            return RetValidate::Illegal(self.wrong_phase_error());
+        };
+        match mov {
+            PureMove::NormalMove(mov) => {
+                match cetkaik_full_state_transition::apply_normal_move(state, *mov, self.config) {
+                    Ok(next_state_p) => RetValidate::Legal {
+                        stepping_ciurl_required: false,
+                        water_entry_ciurl_required: Some(matches!(
+                            next_state_p,
+                            Probabilistic::Water { .. }
+                        )),
+                    },
+                    Err(e) => RetValidate::Illegal(ApiError::new(ErrorCode::IllegalMove, e)),
+                }
+            }
+            PureMove::InfAfterStep(mov) => {
+                match cetkaik_full_state_transition::apply_inf_after_step(state, *mov, self.config)
+                {
+                    Ok(_) => RetValidate::Legal {
+                        stepping_ciurl_required: true,
+                        water_entry_ciurl_required: None,
+                    },
+                    Err(e) => RetValidate::Illegal(ApiError::new(ErrorCode::IllegalMove, e)),
+                }
             }
         }
     }
@@ -301,7 +510,12 @@ impl GameState {
                 Ok(next_state_p) => {
                     let (next_state, ciurl_value) = next_state_p.choose();
                     let ciurl = ciurl_value.map(Ciurl::from);
-                    let move_to_be_polled = self.get_last_move_mut().unwrap();
+                    let Some(move_to_be_polled) = self.get_last_move_mut() else {
+                        return RetAfterHalfAcceptance::Err(ApiError::new(
+                            ErrorCode::InternalError,
+                            "the InfAfterStep to complete was not recorded",
+                        ));
+                    };
                     if let MoveToBePolled::InfAfterStep {
                         src: _,
                         step: _,
@@ -310,38 +524,56 @@ impl GameState {
                         final_result,
                     } = &mut move_to_be_polled.mov
                     {
-                        if let Some(result) = dest.map(|dest_coord| FinalResult {
-                            dest: dest_coord,
-                            water_entry_ciurl: ciurl,
-                            thwarted_by_failing_water_entry_ciurl: ciurl,
-                        }) {
+                        if let Some(result) = dest.map(|dest| FinalResult::new(dest, ciurl)) {
                             final_result.replace(result);
                         }
                     } else {
-                        unreachable!("Invalid MoveToBePolled");
-                    };
+                        return RetAfterHalfAcceptance::Err(ApiError::new(
+                            ErrorCode::InternalError,
+                            "the last move recorded is not an InfAfterStep",
+                        ));
+                    }
                     self.state = Phase::Moved(next_state);
                     match ciurl {
                         Some(ciurl) => RetAfterHalfAcceptance::WithWaterEntry { ciurl },
                         None => RetAfterHalfAcceptance::WithoutWaterEntry,
                     }
                 }
-                Err(e) => RetAfterHalfAcceptance::Err {
-                    why_illegal: e.to_string(),
-                },
+                Err(e) => RetAfterHalfAcceptance::Err(ApiError::new(ErrorCode::IllegalMove, e)),
             }
         } else {
-            RetAfterHalfAcceptance::Err {
-                why_illegal: "Invalid State".to_string(),
+            RetAfterHalfAcceptance::Err(self.wrong_phase_error())
+        }
+    }
+
+    pub fn apply_tymok(&mut self) -> RetTyMok {
+        if let Phase::Moved(state) = &self.state {
+            let state_resolved = cetkaik_full_state_transition::resolve(state, self.config);
+            if let HandResolved::HandExists {
+                if_taxot: _,
+                if_tymok,
+            } = state_resolved
+            {
+                self.set_last_move_status(HandCompletionStatus::TyMok);
+                self.state = Phase::Start(if_tymok);
+                RetTyMok::Ok
+            } else {
+                RetTyMok::Err(ErrorCode::NoHandToResolve.into())
             }
+        } else {
+            RetTyMok::Err(self.wrong_phase_error())
         }
     }
 
-    pub fn apply_tymok(&mut self) {}
     pub fn apply_taxot(&mut self) -> RetTaXot {
         if let Phase::Moved(state) = &self.state {
             let state_resolved = cetkaik_full_state_transition::resolve(state, self.config);
-            if let HandResolved::HandExists { if_taxot, if_tymok: _ } = state_resolved {
+            if let HandResolved::HandExists {
+                if_taxot,
+                if_tymok: _,
+            } = state_resolved
+            {
+                self.set_last_move_status(HandCompletionStatus::TaXot);
                 self.state = match if_taxot {
                     cetkaik_full_state_transition::IfTaxot::NextSeason(p_state) => {
                         Phase::Start(p_state.choose().0)
@@ -357,21 +589,22 @@ impl GameState {
                 if whos_go_first.result != self.is_ia_owner_s_turn() {
                     whos_go_first = whos_go_first.not();
                 }
-                *(self
+                if let Some(slot) = self
                     .is_first_move_ia_move
-                    .lock()
-                    .unwrap()
+                    .lock_or_recover()
                     .get_mut(self.state.get_season().to_index())
-                    .unwrap()) = Some(whos_go_first.clone());
+                {
+                    *slot = Some(whos_go_first.clone());
+                }
 
                 RetTaXot::Ok {
                     is_first_move_my_move: Some(whos_go_first),
                 }
             } else {
-                RetTaXot::Err
+                RetTaXot::Err(ErrorCode::NoHandToResolve.into())
             }
         } else {
-            RetTaXot::Err
+            RetTaXot::Err(self.wrong_phase_error())
         }
     }
 
@@ -382,44 +615,71 @@ impl GameState {
                 HandResolved::NeitherTymokNorTaxot(next_state) => {
                     self.state = Phase::Start(next_state);
                 }
-                HandResolved::HandExists { if_tymok, if_taxot } => {}
-                HandResolved::GameEndsWithoutTymokTaxot(_) => {}
+                HandResolved::HandExists { .. } | HandResolved::GameEndsWithoutTymokTaxot(_) => {}
             }
         }
     }
 
+    /// `None` if who goes first in `season` has not been decided yet.
     #[must_use]
-    pub fn is_first_move_my_move(&self, is_ia_down_for_me: bool, season: usize) -> WhoGoesFirst {
-        let is_first_move_ia_move = self
-            .is_first_move_ia_move
-            .lock()
-            .unwrap()
-            .get_mut(season)
-            .unwrap()
-            .clone()
-            .unwrap();
-        if is_ia_down_for_me {
-            is_first_move_ia_move
-        } else {
-            is_first_move_ia_move.not()
-        }
+    pub fn is_first_move_my_move(
+        &self,
+        is_ia_down_for_me: bool,
+        season: usize,
+    ) -> Option<WhoGoesFirst> {
+        self.is_first_move_ia_move
+            .lock_or_recover()
+            .get(season)
+            .cloned()
+            .flatten()
+            .map(|is_first_move_ia_move| is_first_move_ia_move.seen_by(is_ia_down_for_me))
     }
 
+    /// Decides who goes first in `season`, and returns it from the viewpoint of the IA owner.
     pub fn set_first_mover(
         &mut self,
         season: usize,
         is_ia: bool,
         rng: &mut rand::prelude::ThreadRng,
-    ) {
+    ) -> WhoGoesFirst {
         let mut whos_go_first = WhoGoesFirst::new(rng);
         if whos_go_first.result != is_ia {
             whos_go_first = whos_go_first.not();
         }
-        *(self
-            .is_first_move_ia_move
-            .lock()
-            .unwrap()
-            .get_mut(season)
-            .unwrap()) = Some(whos_go_first);
+        if let Some(slot) = self.is_first_move_ia_move.lock_or_recover().get_mut(season) {
+            *slot = Some(whos_go_first.clone());
+        }
+        whos_go_first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cetkaik_full_state_transition::{message::PureMove, Config};
+
+    use super::GameState;
+    use crate::types::RetValidate;
+
+    #[test]
+    fn the_water_entry_of_an_inf_after_step_is_left_open() {
+        let (initial_state, _) = cetkaik_full_state_transition::initial_state().choose();
+        let (_, candidates) = initial_state.get_candidates(Config::cerke_online_alpha());
+        let game = GameState::new(initial_state, Config::cerke_online_alpha());
+
+        let mut inf_after_steps = 0;
+        for mov in &candidates {
+            let RetValidate::Legal {
+                stepping_ciurl_required,
+                water_entry_ciurl_required,
+            } = game.validate(mov)
+            else {
+                panic!("a candidate must be legal");
+            };
+            let is_inf_after_step = matches!(mov, PureMove::InfAfterStep(_));
+            inf_after_steps += usize::from(is_inf_after_step);
+            assert_eq!(is_inf_after_step, stepping_ciurl_required);
+            assert_eq!(is_inf_after_step, water_entry_ciurl_required.is_none());
+        }
+        assert!(inf_after_steps > 0);
     }
 }
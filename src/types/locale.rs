@@ -0,0 +1,309 @@
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, http::header, FromRequest, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::bot::TacticsKey;
+use super::{
+    ApiError, ErrorCode, RetAfterHalfAcceptance, RetChatPoll, RetGameRecord, RetInfAfterStep,
+    RetInfPoll, RetLegalMoves, RetMainPoll, RetNormalMove, RetRandomCancel, RetRandomPoll,
+    RetRoomState, RetSendChat, RetSpectateEntry, RetSpectatorPoll, RetTaXot, RetTyMok,
+    RetUnlistRoom, RetValidate, RetWhetherTyMokPoll,
+};
+
+/// Language in which human-readable text is sent back.
+/// Chosen by the `lang` query parameter if present, and otherwise by the `Accept-Language` header.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[serde(into = "&'static str")]
+#[serde(try_from = "&str")]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+    Lineparine,
+}
+
+impl TryFrom<&str> for Locale {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let tag = value.trim().to_ascii_lowercase();
+        let primary = tag.split(['-', '_']).next().unwrap_or_default();
+        match primary {
+            "en" => Ok(Locale::En),
+            "ja" => Ok(Locale::Ja),
+            "lineparine" => Ok(Locale::Lineparine),
+            // Lineparine has no ISO 639 code, so it can only come as a private-use tag
+            "x" | "art" if tag.ends_with("-lineparine") => Ok(Locale::Lineparine),
+            _ => Err(format!("unsupported locale `{value}`")),
+        }
+    }
+
+    type Error = String;
+}
+
+impl From<Locale> for &'static str {
+    fn from(a: Locale) -> &'static str {
+        match a {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+            Locale::Lineparine => "x-lineparine",
+        }
+    }
+}
+
+impl Locale {
+    /// Picks the supported language with the highest `q` from an `Accept-Language` header value.
+    #[must_use]
+    pub fn from_accept_language(header_value: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for item in header_value.split(',') {
+            let mut parts = item.split(';');
+            let Ok(locale) = Locale::try_from(parts.next().unwrap_or_default()) else {
+                continue;
+            };
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((locale, q));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+
+    #[must_use]
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        let explicit = req.query_string().split('&').find_map(|pair| {
+            pair.strip_prefix("lang=")
+                .and_then(|lang| Locale::try_from(lang).ok())
+        });
+        explicit
+            .or_else(|| {
+                req.headers()
+                    .get(header::ACCEPT_LANGUAGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(Locale::from_accept_language)
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl FromRequest for Locale {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(Locale::negotiate(req)))
+    }
+}
+
+impl ErrorCode {
+    #[must_use]
+    pub fn message(self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => self.default_message(),
+            Locale::Ja => match self {
+                ErrorCode::UnparsableToken => "アクセストークンを解釈できません",
+                ErrorCode::UnknownToken => "不明なアクセストークンです",
+                ErrorCode::ReadOnlyToken => "このトークンは読み取り専用のため、ここでは使えません",
+                ErrorCode::InvalidToken => "このサーバーが発行したアクセストークンではありません",
+                ErrorCode::ExpiredToken => "アクセストークンの有効期限が切れています",
+                ErrorCode::RevokedToken => "対局が終わったため、アクセストークンは無効になりました",
+                ErrorCode::UnparsableRoomId => "部屋 ID を解釈できません",
+                ErrorCode::UnknownRoom => "その部屋は存在しません",
+                ErrorCode::NotYourTurn => "あなたの手番ではありません",
+                ErrorCode::WrongPhase => "今その操作はできません",
+                ErrorCode::IllegalMove => "その手は指せません",
+                ErrorCode::IllegalMoveKind => "この種類の手はここに送れません",
+                ErrorCode::NoHandToResolve => "役ができていないので再行・終季は選べません",
+                ErrorCode::GameOver => "対局はすでに終わっています",
+                ErrorCode::InvalidChatMessage => "チャットのメッセージが不正です",
+                ErrorCode::RateLimited => "リクエストが多すぎます",
+                ErrorCode::InvalidRequest => "リクエストの本文を読み取れません",
+                ErrorCode::InternalError => "サーバー内部でエラーが発生しました",
+            },
+            Locale::Lineparine => match self {
+                ErrorCode::UnparsableToken => "Ers sysnul'd niv lkurf.",
+                ErrorCode::UnknownToken => "Mi niv firlex ers sysnul'd.",
+                ErrorCode::ReadOnlyToken => "Ers sysnul es fal fenxe, edioll niv lex.",
+                ErrorCode::InvalidToken => "Ers sysnul niv fal mi'd.",
+                ErrorCode::ExpiredToken => "Ers sysnul'd stysnel tydiest.",
+                ErrorCode::RevokedToken => "Cerke tydiest, ers sysnul niv fal.",
+                ErrorCode::UnparsableRoomId => "Ers lersse'd mels niv lkurf.",
+                ErrorCode::UnknownRoom => "Ers lersse niv es.",
+                ErrorCode::NotYourTurn => "Co'd lyjot niv es.",
+                ErrorCode::WrongPhase => "Xale co niv edioll lex.",
+                ErrorCode::IllegalMove => "Co niv edioll ers lyjot.",
+                ErrorCode::IllegalMoveKind => "Ers lyjot'd tonir niv fal lex.",
+                ErrorCode::NoHandToResolve => "Ers niv es, ty mok ad ta xot niv edioll.",
+                ErrorCode::GameOver => "Cerke tydiest.",
+                ErrorCode::InvalidChatMessage => "Ers lkurftlesse niv fal.",
+                ErrorCode::RateLimited => "Co lkurf fai xale.",
+                ErrorCode::InvalidRequest => "Mi niv edioll lkurf ers lkurftlesse.",
+                ErrorCode::InternalError => "Mi'd sietiver es niv fal.",
+            },
+        }
+    }
+}
+
+impl TacticsKey {
+    #[must_use]
+    pub fn message(self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => match self {
+                TacticsKey::VictoryAlmostCertain => "Victory is almost certain.",
+                TacticsKey::StrengthenedShaman => "My Shaman is strengthened.",
+                TacticsKey::FreeLunch => "A free lunch!",
+                TacticsKey::AvoidDefeat => "I must avoid defeat.",
+                TacticsKey::LossAlmostCertain => "Loss is almost certain...",
+                TacticsKey::Neutral => "Hmm.",
+            },
+            Locale::Ja => match self {
+                TacticsKey::VictoryAlmostCertain => "勝利はほぼ確実だ。",
+                TacticsKey::StrengthenedShaman => "巫が強化された。",
+                TacticsKey::FreeLunch => "タダ取りだ！",
+                TacticsKey::AvoidDefeat => "負けだけは避けねば。",
+                TacticsKey::LossAlmostCertain => "負けはほぼ確実か……",
+                TacticsKey::Neutral => "ふむ。",
+            },
+            Locale::Lineparine => match self {
+                TacticsKey::VictoryAlmostCertain => "Mi'd sietiver es cene.",
+                TacticsKey::StrengthenedShaman => "Mi'd tuk2 es lanerme.",
+                TacticsKey::FreeLunch => "Xace!",
+                TacticsKey::AvoidDefeat => "Mi veles niv tydiest.",
+                TacticsKey::LossAlmostCertain => "Mi'd liaxa es cene...",
+                TacticsKey::Neutral => "Ja.",
+            },
+        }
+    }
+}
+
+/// Rewrites the human-readable parts of a response into the given language.
+/// English responses are left untouched, since they may carry more detail than the catalog.
+pub trait Localize {
+    #[must_use]
+    fn localize(self, locale: Locale) -> Self;
+}
+
+impl Localize for ApiError {
+    fn localize(self, locale: Locale) -> Self {
+        if locale == Locale::En {
+            self
+        } else {
+            ApiError::new(self.code, self.code.message(locale))
+        }
+    }
+}
+
+impl Localize for RetMainPoll {
+    fn localize(self, locale: Locale) -> Self {
+        match self {
+            RetMainPoll::MoveMade {
+                content, message, ..
+            } => RetMainPoll::MoveMade {
+                content,
+                message,
+                localized_message: message.map(|key| key.message(locale).to_string()),
+            },
+            RetMainPoll::Err(e) => RetMainPoll::Err(e.localize(locale)),
+            RetMainPoll::NotYetDetermined => RetMainPoll::NotYetDetermined,
+        }
+    }
+}
+
+impl Localize for RetValidate {
+    fn localize(self, locale: Locale) -> Self {
+        match self {
+            RetValidate::Illegal(e) => RetValidate::Illegal(e.localize(locale)),
+            RetValidate::Err(e) => RetValidate::Err(e.localize(locale)),
+            legal @ RetValidate::Legal { .. } => legal,
+        }
+    }
+}
+
+macro_rules! impl_localize_for_err {
+    ($($t:ident),*) => {
+        $(
+            impl Localize for $t {
+                fn localize(self, locale: Locale) -> Self {
+                    match self {
+                        $t::Err(e) => $t::Err(e.localize(locale)),
+                        other => other,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_localize_for_err!(
+    RetAfterHalfAcceptance,
+    RetChatPoll,
+    RetGameRecord,
+    RetInfAfterStep,
+    RetInfPoll,
+    RetLegalMoves,
+    RetNormalMove,
+    RetRandomCancel,
+    RetRandomPoll,
+    RetRoomState,
+    RetSendChat,
+    RetSpectateEntry,
+    RetSpectatorPoll,
+    RetTaXot,
+    RetTyMok,
+    RetUnlistRoom,
+    RetWhetherTyMokPoll
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{Locale, Localize};
+    use crate::types::{ApiError, ErrorCode, RetTyMok};
+
+    #[test]
+    fn accept_language_negotiation() {
+        assert_eq!(
+            Some(Locale::Ja),
+            Locale::from_accept_language("ja-JP,ja;q=0.9,en-US;q=0.8,en;q=0.7")
+        );
+        assert_eq!(
+            Some(Locale::En),
+            Locale::from_accept_language("fr-FR, en;q=0.5, ja;q=0.3")
+        );
+        assert_eq!(
+            Some(Locale::Lineparine),
+            Locale::from_accept_language("art-x-lineparine, ja;q=0.9")
+        );
+        assert_eq!(Ok(Locale::Lineparine), Locale::try_from("x-lineparine"));
+        assert_eq!(Ok(Locale::Lineparine), Locale::try_from("lineparine"));
+        assert!(Locale::try_from("x-klingon").is_err());
+        assert_eq!(None, Locale::from_accept_language("fr, de;q=0.5"));
+        assert_eq!(None, Locale::from_accept_language("ja;q=0"));
+    }
+
+    #[test]
+    fn lang_parameter_overrides_accept_language() {
+        use actix_web::test::TestRequest;
+        let req = TestRequest::default()
+            .uri("/poll/main?lang=x-lineparine")
+            .insert_header(("Accept-Language", "ja"))
+            .to_http_request();
+        assert_eq!(Locale::Lineparine, Locale::negotiate(&req));
+    }
+
+    #[test]
+    fn localized_error() {
+        let ret = RetTyMok::Err(ApiError::new(
+            ErrorCode::NotYourTurn,
+            "it's not your turn; detailed explanation here",
+        ));
+        assert_eq!(
+            RetTyMok::Err(ApiError::new(
+                ErrorCode::NotYourTurn,
+                "あなたの手番ではありません"
+            )),
+            ret.localize(Locale::Ja)
+        );
+    }
+}
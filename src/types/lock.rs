@@ -0,0 +1,32 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locking that survives a panic in another request.
+/// A poisoned mutex would otherwise make every later request touching it panic as well,
+/// so that a single bad room could take down the whole server.
+pub trait LockOrRecover<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockOrRecover<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LockOrRecover;
+    use std::sync::Mutex;
+
+    #[test]
+    fn poisoned_lock_is_recovered() {
+        let mutex = Mutex::new(0);
+        let _ = std::panic::catch_unwind(|| {
+            let mut guard = mutex.lock().unwrap();
+            *guard += 1;
+            panic!("poisoning the lock");
+        });
+        assert!(mutex.is_poisoned());
+        assert_eq!(1, *mutex.lock_or_recover());
+    }
+}
@@ -1,11 +1,16 @@
-use std::fmt::Debug;
+use super::serde_coord;
+use super::{
+    bot::{BotDifficulty, TacticsKey},
+    AbsoluteCoord, ApiError, ChatMessage, Ciurl, HandCompletionStatus, NonTamMoveDotData,
+    NormalMove, RoomId, RoomState, TamMoveInternal,
+};
 use rand::prelude::ThreadRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use super::{AbsoluteCoord, Ciurl, NonTamMoveDotData, TamMoveInternal, bot::TacticsKey};
-use super::serde_coord;
+use std::fmt::Debug;
 
 /* InfAfterStep | AfterHalfAcceptance | NormalMove*/
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(tag = "type")]
 pub enum MainMessage {
     InfAfterStep {
@@ -26,7 +31,11 @@ impl MainMessage {
     pub fn process(self) -> MoveToBePolled {
         match self {
             MainMessage::InfAfterStep { flatten } => {
-                let InfAfterStepInternal {src,step, coord_signifying_planned_direction} = flatten;
+                let InfAfterStepInternal {
+                    src,
+                    step,
+                    coord_signifying_planned_direction,
+                } = flatten;
                 MoveToBePolled::InfAfterStep {
                     src,
                     step,
@@ -34,73 +43,105 @@ impl MainMessage {
                     stepping_ciurl: Ciurl::new(&mut rand::thread_rng()),
                     final_result: None,
                 }
+            }
+            MainMessage::NonTamMove { data } => MoveToBePolled::NonTamMove { data },
+            MainMessage::TamMove { flatten } => MoveToBePolled::TamMove { flatten },
+        }
+    }
+}
+
+impl From<cetkaik_full_state_transition::message::PureMove> for MainMessage {
+    fn from(pure_move: cetkaik_full_state_transition::message::PureMove) -> Self {
+        use cetkaik_full_state_transition::message::PureMove;
+        match pure_move {
+            PureMove::InfAfterStep(m) => MainMessage::InfAfterStep {
+                flatten: InfAfterStepInternal {
+                    src: m.src,
+                    step: m.step,
+                    coord_signifying_planned_direction: m.planned_direction,
+                },
             },
-            MainMessage::NonTamMove { data } => {
-                MoveToBePolled::NonTamMove {
-                    data
-                }
+            PureMove::NormalMove(m) => match NormalMove::from(m) {
+                NormalMove::NonTamMove { data } => MainMessage::NonTamMove { data },
+                NormalMove::TamMove { flatten } => MainMessage::TamMove { flatten },
             },
+        }
+    }
+}
+
+impl From<MainMessage> for cetkaik_full_state_transition::message::PureMove {
+    fn from(message: MainMessage) -> Self {
+        use cetkaik_full_state_transition::message::{InfAfterStep, PureMove};
+        match message {
+            MainMessage::InfAfterStep { flatten } => PureMove::InfAfterStep(InfAfterStep {
+                src: flatten.src,
+                step: flatten.step,
+                planned_direction: flatten.coord_signifying_planned_direction,
+            }),
+            MainMessage::NonTamMove { data } => {
+                PureMove::NormalMove(NormalMove::NonTamMove { data }.into())
+            }
             MainMessage::TamMove { flatten } => {
-                MoveToBePolled::TamMove {
-                    flatten
-                }
-            },
+                PureMove::NormalMove(NormalMove::TamMove { flatten }.into())
+            }
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, JsonSchema)]
 pub struct MainMessageStruct {
-    pub message: MainMessage, 
+    pub message: MainMessage,
 }
 impl From<MainMessage> for MainMessageStruct {
     fn from(message: MainMessage) -> Self {
-        Self { 
-            message
-        }
+        Self { message }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{AfterHalfAcceptanceMessageStruct, MainMessage, MainMessageStruct};
-    use crate::types::{AfterHalfAcceptanceMessage, NonTamMoveDotData};
+    use crate::types::{
+        AfterHalfAcceptanceMessage, AfterHalfAcceptanceMessageStruct, MainMessage,
+        MainMessageStruct, NonTamMoveDotData,
+    };
 
     #[test]
-    fn test_ser_normalmove_nontam(){
-        use cetkaik_core::absolute::{Row,Column};
-        let mms :MainMessageStruct = MainMessage::NonTamMove {
-            data : NonTamMoveDotData::SrcDst {
-                src: cetkaik_core::absolute::Coord(Row::AI,Column::C),
-                dest: cetkaik_core::absolute::Coord(Row::Y,Column::C),
-                water_entry_ciurl: None
+    fn test_ser_normalmove_nontam() {
+        use cetkaik_core::absolute::{Column, Row};
+        let mms: MainMessageStruct = MainMessage::NonTamMove {
+            data: NonTamMoveDotData::SrcDst {
+                src: cetkaik_core::absolute::Coord(Row::AI, Column::C),
+                dest: cetkaik_core::absolute::Coord(Row::Y, Column::C),
+                water_entry_ciurl: None,
             },
-        }.into();
+        }
+        .into();
         let json_str = r#"{"message":{"type":"NonTamMove","data":{"type":"SrcDst","src":["AI","C"],"dest":["Y","C"]}}}"#;
-        assert_eq!(json_str, serde_json::to_string(&mms).unwrap() );
-
+        assert_eq!(json_str, serde_json::to_string(&mms).unwrap());
     }
 
     #[test]
-    fn test_normalmove_nontam(){
-        use cetkaik_core::absolute::{Row,Column};
+    fn test_normalmove_nontam() {
+        use cetkaik_core::absolute::{Column, Row};
         let json_str = r#"{"message":{"type":"NonTamMove","data":{"type":"SrcDst","src":["AI","C"],"dest":["O","C"]}}}"#;
         let result: MainMessageStruct = serde_json::from_str(json_str).unwrap();
-        let MainMessageStruct{ message: result } = result;
-
-        assert_eq!(result, MainMessage::NonTamMove {
-            data : NonTamMoveDotData::SrcDst {
-                src: cetkaik_core::absolute::Coord(Row::AI,Column::C),
-                dest: cetkaik_core::absolute::Coord(Row::O,Column::C),
-                water_entry_ciurl: None
-            },
-        });
-
+        let MainMessageStruct { message: result } = result;
+
+        assert_eq!(
+            result,
+            MainMessage::NonTamMove {
+                data: NonTamMoveDotData::SrcDst {
+                    src: cetkaik_core::absolute::Coord(Row::AI, Column::C),
+                    dest: cetkaik_core::absolute::Coord(Row::O, Column::C),
+                    water_entry_ciurl: None
+                },
+            }
+        );
     }
 
     #[test]
-    fn test_after_half_null(){
-        use cetkaik_core::absolute::{Row,Column};
+    fn test_after_half_null() {
+        use cetkaik_core::absolute::{Column, Row};
         {
             let json_str = r#"{
             "message": {
@@ -109,11 +150,12 @@ mod tests {
             }
         }"#;
             let result: AfterHalfAcceptanceMessageStruct = serde_json::from_str(json_str).unwrap();
-            let AfterHalfAcceptanceMessageStruct{ message: result } = result;
+            let AfterHalfAcceptanceMessageStruct { message: result } = result;
 
-            assert_eq!(result, AfterHalfAcceptanceMessage::AfterHalfAcceptance {
-                dest: None
-            });
+            assert_eq!(
+                result,
+                AfterHalfAcceptanceMessage::AfterHalfAcceptance { dest: None }
+            );
         }
         {
             let json_str = r#"{
@@ -126,42 +168,49 @@ mod tests {
             }
         }"#;
             let result: AfterHalfAcceptanceMessageStruct = serde_json::from_str(json_str).unwrap();
-            let AfterHalfAcceptanceMessageStruct{ message: result } = result;
+            let AfterHalfAcceptanceMessageStruct { message: result } = result;
 
-            assert_eq!(result, AfterHalfAcceptanceMessage::AfterHalfAcceptance {
-                dest: Some(cetkaik_core::absolute::Coord(Row::O,Column::L)),
-            });
+            assert_eq!(
+                result,
+                AfterHalfAcceptanceMessage::AfterHalfAcceptance {
+                    dest: Some(cetkaik_core::absolute::Coord(Row::O, Column::L)),
+                }
+            );
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(tag = "type")]
 pub enum AfterHalfAcceptanceMessage {
     AfterHalfAcceptance {
-        #[serde(with="serde_coord::opt")]
-        dest: Option<AbsoluteCoord>
+        #[serde(with = "serde_coord::opt")]
+        #[schemars(with = "Option<serde_coord::CoordSchema>")]
+        dest: Option<AbsoluteCoord>,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, JsonSchema)]
 pub struct AfterHalfAcceptanceMessageStruct {
-    pub message: AfterHalfAcceptanceMessage, 
+    pub message: AfterHalfAcceptanceMessage,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone, JsonSchema)]
 pub struct InfAfterStepInternal {
-    #[serde(with="serde_coord")]
+    #[serde(with = "serde_coord")]
+    #[schemars(with = "serde_coord::CoordSchema")]
     pub src: AbsoluteCoord,
-    #[serde(with="serde_coord")]
+    #[serde(with = "serde_coord")]
+    #[schemars(with = "serde_coord::CoordSchema")]
     pub step: AbsoluteCoord,
 
-    #[serde(with="serde_coord")]
+    #[serde(with = "serde_coord")]
+    #[schemars(with = "serde_coord::CoordSchema")]
     #[serde(rename = "plannedDirection")]
     pub coord_signifying_planned_direction: AbsoluteCoord,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct WhoGoesFirst {
     pub result: bool,
     pub process: Vec<[Ciurl; 2]>,
@@ -197,48 +246,73 @@ impl WhoGoesFirst {
             result: !self.result,
         }
     }
+
+    /// `self` being from the viewpoint of the IA owner, returns it as seen by the given side.
+    #[must_use]
+    pub fn seen_by(&self, is_ia_down_for_me: bool) -> Self {
+        if is_ia_down_for_me {
+            self.clone()
+        } else {
+            self.not()
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetTyMok {
-    Err,
+    Err(ApiError),
     Ok,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetTaXot {
-    Err,
+    Err(ApiError),
     Ok {
         is_first_move_my_move: Option<WhoGoesFirst>,
     },
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetInfAfterStep {
     Ok { ciurl: Ciurl },
-    Err { why_illegal: String },
+    Err(ApiError),
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetNormalMove {
-    Err { why_illegal: String },
+    Err(ApiError),
     WithWaterEntry { ciurl: Ciurl },
     WithoutWaterEntry,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetValidate {
+    Legal {
+        /// Whether the stepping ciurl would be cast (only for `InfAfterStep`)
+        stepping_ciurl_required: bool,
+
+        /// Whether the water entry ciurl would be cast. For an `InfAfterStep`, `None`,
+        /// since where the piece ends up is not known until the stepping ciurl is cast
+        water_entry_ciurl_required: Option<bool>,
+    },
+    Illegal(ApiError),
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetAfterHalfAcceptance {
-    Err { why_illegal: String },
+    Err(ApiError),
     WithWaterEntry { ciurl: Ciurl },
     WithoutWaterEntry,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetRandomEntry {
     InWaitingList {
@@ -255,7 +329,7 @@ pub enum RetRandomEntry {
     },
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetVsCpuEntry {
     LetTheGameBegin {
@@ -267,21 +341,21 @@ pub enum RetVsCpuEntry {
     },
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetRandomPoll {
-    Err { why_illegal: String },
+    Err(ApiError),
     Ok { ret: RetRandomEntry },
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetRandomCancel {
-    Err { why_illegal: String },
+    Err(ApiError),
     Ok { cancellable: bool },
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetWhetherTyMokPoll {
     TyMok,
@@ -289,33 +363,125 @@ pub enum RetWhetherTyMokPoll {
         is_first_move_my_move: Option<WhoGoesFirst>,
     },
     NotYetDetermined,
-    Err {
-        why_illegal: String,
-    },
+    Err(ApiError),
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetMainPoll {
     MoveMade {
         content: MoveToBePolled,
         message: Option<TacticsKey>,
+
+        /// `message` rendered in the negotiated language
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        localized_message: Option<String>,
     },
     NotYetDetermined,
-    Err {
-        why_illegal: String,
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetSpectateEntry {
+    Ok { spectator_token: String },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetSpectatorPoll {
+    Ok {
+        current_season: usize,
+
+        /// One entry per season played so far, including the current one.
+        seasons: Vec<SpectatedSeason>,
+    },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetGameRecord {
+    Ok {
+        seasons: Vec<SpectatedSeason>,
+        chat: Vec<ChatMessage>,
     },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct SpectatedSeason {
+    #[serde(rename = "is_first_move_IA_move")]
+    pub is_first_move_ia_move: Option<WhoGoesFirst>,
+    pub moves: Vec<SpectatedMove>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct SpectatedMove {
+    #[serde(rename = "by_IA_owner")]
+    pub by_ia_owner: bool,
+    pub content: MoveToBePolled,
+
+    /// Whether the player chose ty mok or ta xot after completing a hand with this move
+    pub status: Option<HandCompletionStatus>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetLiveRooms {
+    Ok { rooms: Vec<LiveRoom> },
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct LiveRoom {
+    pub room_id: RoomId,
+    pub season: usize,
+
+    #[serde(rename = "IA_owner_s_score")]
+    pub ia_owner_s_score: i32,
+
+    #[serde(rename = "A_owner_s_score")]
+    pub a_owner_s_score: i32,
+    pub opponent_is_bot: bool,
+    pub move_count: usize,
+    pub elapsed_secs: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetUnlistRoom {
+    Ok,
+    Err(ApiError),
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetRoomState {
+    Ok { state: RoomState },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum RetLegalMoves {
+    /// Each candidate has the same shape as the body of `/decision/main`
+    Ok {
+        hop1zuo1_candidates: Vec<MainMessage>,
+        candidates: Vec<MainMessage>,
+    },
+    Err(ApiError),
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum RetInfPoll {
     MoveMade { content: MoveToBePolled },
     NotYetDetermined,
-    Err { why_illegal: String },
+    Err(ApiError),
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
 #[serde(tag = "type")]
 pub enum MoveToBePolled {
     NonTamMove {
@@ -326,12 +492,15 @@ pub enum MoveToBePolled {
         flatten: TamMoveInternal,
     },
     InfAfterStep {
-        #[serde(with="serde_coord")]
+        #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         src: AbsoluteCoord,
-        #[serde(with="serde_coord")]
+        #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         step: AbsoluteCoord,
 
-        #[serde(with="serde_coord")]
+        #[serde(with = "serde_coord")]
+        #[schemars(with = "serde_coord::CoordSchema")]
         #[serde(rename = "plannedDirection")]
         coord_signifying_planned_direction: AbsoluteCoord,
         stepping_ciurl: Ciurl,
@@ -343,30 +512,67 @@ pub enum MoveToBePolled {
 
 impl From<NonTamMoveDotData> for MoveToBePolled {
     fn from(mov: NonTamMoveDotData) -> Self {
-        Self::NonTamMove {
-            data: mov
-        }
+        Self::NonTamMove { data: mov }
     }
 }
 impl From<TamMoveInternal> for MoveToBePolled {
     fn from(mov: TamMoveInternal) -> Self {
-        Self::TamMove {
-            flatten: mov
-        }
+        Self::TamMove { flatten: mov }
     }
 }
 
-
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
-pub struct FinalResult {    
-    #[serde(with="serde_coord")]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct FinalResult {
+    #[serde(with = "serde_coord")]
+    #[schemars(with = "serde_coord::CoordSchema")]
     pub dest: AbsoluteCoord,
     pub water_entry_ciurl: Option<Ciurl>,
     pub thwarted_by_failing_water_entry_ciurl: Option<Ciurl>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+impl FinalResult {
+    /// Files the water entry ciurl, if any was cast, under whether it let the piece in.
+    #[must_use]
+    pub fn new(dest: AbsoluteCoord, water_entry_ciurl: Option<Ciurl>) -> Self {
+        let (entered, thwarted) = match water_entry_ciurl {
+            Some(ciurl) if ciurl.count() < 3 => (None, Some(ciurl)),
+            ciurl => (ciurl, None),
+        };
+        FinalResult {
+            dest,
+            water_entry_ciurl: entered,
+            thwarted_by_failing_water_entry_ciurl: thwarted,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, JsonSchema)]
 
 pub struct MsgWithAccessToken {
     pub access_token: String,
 }
+
+/// The body of `/matching/random/entry`, which may as well be left out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, JsonSchema)]
+pub struct MsgRandomEntry {
+    /// Keeps the room out of `/rooms/live` and away from spectators from the start.
+    /// The room is unlisted if either player asks for it.
+    #[serde(default)]
+    pub unlisted: bool,
+}
+
+/// The body of `/matching/vs_cpu/entry`, which may as well be left out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, JsonSchema)]
+pub struct MsgVsCpuEntry {
+    #[serde(default)]
+    pub difficulty: BotDifficulty,
+
+    /// Keeps the room out of `/rooms/live` and away from spectators from the start
+    #[serde(default)]
+    pub unlisted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, JsonSchema)]
+pub struct MsgWithRoomId {
+    pub room_id: String,
+}
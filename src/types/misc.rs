@@ -1,11 +1,10 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use uuid::Uuid;
 
-
-
 /// A type that serialize into `{}`.
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, JsonSchema)]
 pub struct Unit {}
 
 #[cfg(test)]
@@ -20,11 +19,18 @@ mod tests {
     }
 }
 
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, JsonSchema)]
 
 pub struct RoomId(pub Uuid);
 
+impl RoomId {
+    /// # Errors
+    /// Returns `Err` if the Uuid is not valid
+    pub fn parse_str(s: &str) -> Result<Self, uuid::Error> {
+        Ok(Self(Uuid::parse_str(s)?))
+    }
+}
+
 impl std::fmt::Display for RoomId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -44,12 +50,30 @@ impl AccessToken {
 
 impl std::fmt::Display for AccessToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0.hyphenated().to_string())
+        write!(f, "{}", self.0.hyphenated())
+    }
+}
+
+/// A read-only token handed to spectators. Unlike `AccessToken`, it is tied to a room but not to a side.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct SpectatorToken(pub Uuid);
+
+impl SpectatorToken {
+    /// # Errors
+    /// Returns `Err` if the Uuid is not valid
+    pub fn parse_str(s: &str) -> Result<Self, uuid::Error> {
+        Ok(Self(Uuid::parse_str(s)?))
+    }
+}
+
+impl std::fmt::Display for SpectatorToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.hyphenated())
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct RoomInfoWithPerspective {
-    pub room_id: RoomId, 
+    pub room_id: RoomId,
     pub is_ia_down_for_me: bool,
-}
\ No newline at end of file
+}
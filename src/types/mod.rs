@@ -1,14 +1,25 @@
 pub mod app_state;
-pub mod misc;
 pub mod bot;
+pub mod chat;
+pub mod error;
 pub mod game;
-pub mod message;
 pub mod game_state;
+pub mod locale;
+pub mod lock;
+pub mod message;
+pub mod misc;
+pub mod schema;
 pub mod serde_coord;
+pub mod token;
 
 pub use app_state::AppState;
 pub use bot::BotToken;
-pub use misc::*;
+pub use chat::*;
+pub use error::{ApiError, ErrorCode};
 pub use game::*;
 pub use game_state::GameState;
-pub use message::*;
\ No newline at end of file
+pub use locale::{Locale, Localize};
+pub use lock::LockOrRecover;
+pub use message::*;
+pub use misc::*;
+pub use token::{TokenClaims, TokenSigner};
@@ -0,0 +1,96 @@
+//! `JsonSchema` impls for the types whose wire format is not what `#[derive(JsonSchema)]` would infer,
+//! i.e. the ones serialized through `serde(into = "&'static str")` or `serde_repr`.
+
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+    JsonSchema,
+};
+
+use super::{bot::TacticsKey, Color, Profession, QuickPhrase};
+
+#[must_use]
+pub fn string_enum_schema(values: &[&'static str]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.iter().map(|value| (*value).into()).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn integer_enum_schema(values: &[u8]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        enum_values: Some(values.iter().map(|value| (*value).into()).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
+impl JsonSchema for TacticsKey {
+    fn schema_name() -> String {
+        "TacticsKey".to_owned()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_enum_schema(&[
+            TacticsKey::VictoryAlmostCertain.into(),
+            TacticsKey::StrengthenedShaman.into(),
+            TacticsKey::FreeLunch.into(),
+            TacticsKey::AvoidDefeat.into(),
+            TacticsKey::LossAlmostCertain.into(),
+            TacticsKey::Neutral.into(),
+        ])
+    }
+}
+
+impl JsonSchema for QuickPhrase {
+    fn schema_name() -> String {
+        "QuickPhrase".to_owned()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        string_enum_schema(&[
+            QuickPhrase::Greetings.into(),
+            QuickPhrase::GoodLuck.into(),
+            QuickPhrase::GoodGame.into(),
+            QuickPhrase::WellPlayed.into(),
+            QuickPhrase::Thanks.into(),
+            QuickPhrase::Sorry.into(),
+            QuickPhrase::PleaseWait.into(),
+            QuickPhrase::Oops.into(),
+        ])
+    }
+}
+
+impl JsonSchema for Color {
+    fn schema_name() -> String {
+        "Color".to_owned()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        integer_enum_schema(&[Color::Kok1 as u8, Color::Huok2 as u8])
+    }
+}
+
+impl JsonSchema for Profession {
+    fn schema_name() -> String {
+        "Profession".to_owned()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        integer_enum_schema(&[
+            Profession::Nuak1 as u8,
+            Profession::Kauk2 as u8,
+            Profession::Gua2 as u8,
+            Profession::Kaun1 as u8,
+            Profession::Dau2 as u8,
+            Profession::Maun1 as u8,
+            Profession::Kua2 as u8,
+            Profession::Tuk2 as u8,
+            Profession::Uai1 as u8,
+            Profession::Io as u8,
+        ])
+    }
+}
@@ -116,3 +116,31 @@ where
         opt.ok_or_else(|| serde::de::Error::invalid_value(Unexpected::Other("null"), &"[str,str]"))
     })
 }
+
+/// Stands in for `AbsoluteCoord` when generating JSON Schemas; a coordinate is sent as `[row, column]`, e.g. `["AI", "C"]`.
+pub struct CoordSchema;
+
+impl schemars::JsonSchema for CoordSchema {
+    fn schema_name() -> String {
+        "Coord".to_owned()
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use super::schema::string_enum_schema;
+        use schemars::schema::{ArrayValidation, InstanceType, SchemaObject};
+
+        let rows = string_enum_schema(&["A", "E", "I", "U", "O", "Y", "AI", "AU", "IA"]);
+        let columns = string_enum_schema(&["K", "L", "N", "T", "Z", "X", "C", "M", "P"]);
+        SchemaObject {
+            instance_type: Some(InstanceType::Array.into()),
+            array: Some(Box::new(ArrayValidation {
+                items: Some(vec![rows, columns].into()),
+                min_items: Some(2),
+                max_items: Some(2),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
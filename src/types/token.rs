@@ -0,0 +1,250 @@
+//! Signed access tokens.
+//!
+//! What the client holds is not the bare `AccessToken` but an HS256 JWT whose subject is that
+//! `AccessToken`, so that a token lifted from a log stops working once it expires or once its game is over.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use super::{AccessToken, ApiError, ErrorCode, RoomId, RoomInfoWithPerspective};
+
+/// `{"alg":"HS256","typ":"JWT"}`, which is the only header we ever issue or accept
+const HEADER: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9";
+
+/// How long a token stays valid unless `TOKEN_LIFETIME_SECS` says otherwise
+pub const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_hours(24);
+
+/// How long the tokens of a finished game keep working, so that both sides can still poll the last move
+pub const REVOCATION_GRACE_PERIOD: Duration = Duration::from_mins(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TokenClaims {
+    pub sub: Uuid,
+
+    /// Absent while waiting in random matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<Uuid>,
+
+    #[serde(rename = "is_IA_down_for_me")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_ia_down_for_me: Option<bool>,
+
+    /// Seconds since the Unix epoch
+    pub exp: u64,
+}
+
+impl TokenClaims {
+    #[must_use]
+    pub fn access_token(&self) -> AccessToken {
+        AccessToken(self.sub)
+    }
+
+    /// Whether the claims were issued for this very room and side
+    #[must_use]
+    pub fn matches(&self, room_info: &RoomInfoWithPerspective) -> bool {
+        self.room == Some(room_info.room_id.0)
+            && self.is_ia_down_for_me == Some(room_info.is_ia_down_for_me)
+    }
+
+    #[must_use]
+    pub fn room_id(&self) -> Option<RoomId> {
+        self.room.map(RoomId)
+    }
+}
+
+pub struct TokenSigner {
+    key: Vec<u8>,
+    lifetime: Duration,
+}
+
+impl Default for TokenSigner {
+    /// A signer with a fresh random key; tokens do not survive a restart.
+    fn default() -> Self {
+        let mut key = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self {
+            key,
+            lifetime: DEFAULT_TOKEN_LIFETIME,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl TokenSigner {
+    #[must_use]
+    pub fn new(key: impl Into<Vec<u8>>, lifetime: Duration) -> Self {
+        Self {
+            key: key.into(),
+            lifetime,
+        }
+    }
+
+    /// Reads the key from `TOKEN_SECRET` and the lifetime from `TOKEN_LIFETIME_SECS`.
+    /// Without `TOKEN_SECRET`, a random key is used, which is fine as long as there is a single instance.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut signer = match std::env::var("TOKEN_SECRET") {
+            Ok(secret) if !secret.is_empty() => Self::new(secret, DEFAULT_TOKEN_LIFETIME),
+            _ => Self::default(),
+        };
+        if let Some(secs) = std::env::var("TOKEN_LIFETIME_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+        {
+            signer.lifetime = Duration::from_secs(secs);
+        }
+        signer
+    }
+
+    fn mac(&self, signing_input: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(signing_input.as_bytes());
+        mac
+    }
+
+    /// Issues a token for `access_token`, bound to the room and side if one has been assigned.
+    #[must_use]
+    pub fn issue(
+        &self,
+        access_token: AccessToken,
+        room_info: Option<&RoomInfoWithPerspective>,
+    ) -> String {
+        let claims = TokenClaims {
+            sub: access_token.0,
+            room: room_info.map(|info| info.room_id.0),
+            is_ia_down_for_me: room_info.map(|info| info.is_ia_down_for_me),
+            exp: now() + self.lifetime.as_secs(),
+        };
+        self.sign(&claims)
+    }
+
+    #[must_use]
+    pub fn sign(&self, claims: &TokenClaims) -> String {
+        let payload = URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(claims).expect("claims are always serializable"));
+        let signing_input = format!("{HEADER}.{payload}");
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&signing_input).finalize().into_bytes());
+        format!("{signing_input}.{signature}")
+    }
+
+    /// Checks the signature and the expiry. Whether the token still refers to a live room is up to the caller.
+    ///
+    /// # Errors
+    /// `UnparsableToken` if the token is not a JWT we could have issued,
+    /// `InvalidToken` if the signature does not match, and `ExpiredToken` if it has expired.
+    pub fn verify(&self, raw_token: &str) -> Result<TokenClaims, ApiError> {
+        let unparsable = || {
+            ApiError::new(
+                ErrorCode::UnparsableToken,
+                format!("Unparsable access token `{raw_token}`"),
+            )
+        };
+        let (signing_input, signature) = raw_token.rsplit_once('.').ok_or_else(unparsable)?;
+        let (header, payload) = signing_input.split_once('.').ok_or_else(unparsable)?;
+        if header != HEADER {
+            return Err(unparsable());
+        }
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| unparsable())?;
+        self.mac(signing_input)
+            .verify_slice(&signature)
+            .map_err(|_| ApiError::from(ErrorCode::InvalidToken))?;
+
+        let claims: TokenClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(unparsable)?;
+        if claims.exp <= now() {
+            return Err(ErrorCode::ExpiredToken.into());
+        }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{TokenClaims, TokenSigner, HEADER};
+    use crate::types::{AccessToken, ErrorCode, RoomId, RoomInfoWithPerspective};
+    use uuid::Uuid;
+
+    #[test]
+    fn header_is_hs256() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+        assert_eq!(
+            br#"{"alg":"HS256","typ":"JWT"}"#.to_vec(),
+            URL_SAFE_NO_PAD.decode(HEADER).unwrap()
+        );
+    }
+
+    #[test]
+    fn issue_and_verify() {
+        let signer = TokenSigner::new("secret", Duration::from_mins(1));
+        let room_info = RoomInfoWithPerspective {
+            room_id: RoomId(Uuid::new_v4()),
+            is_ia_down_for_me: true,
+        };
+        let access_token = AccessToken(Uuid::new_v4());
+        let token = signer.issue(access_token, Some(&room_info));
+
+        let claims = signer.verify(&token).unwrap();
+        assert_eq!(access_token, claims.access_token());
+        assert!(claims.matches(&room_info));
+        assert!(!claims.matches(&RoomInfoWithPerspective {
+            is_ia_down_for_me: false,
+            ..room_info
+        }));
+
+        let other_signer = TokenSigner::new("another secret", Duration::from_mins(1));
+        assert_eq!(
+            ErrorCode::InvalidToken,
+            other_signer.verify(&token).unwrap_err().code
+        );
+
+        // swapping in another payload must break the signature
+        let forged = signer.sign(&TokenClaims {
+            sub: Uuid::new_v4(),
+            ..claims.clone()
+        });
+        let (_, forged_payload, _) = {
+            let mut parts = forged.split('.');
+            (parts.next(), parts.next().unwrap(), parts.next())
+        };
+        let mut parts = token.split('.');
+        let tampered = format!(
+            "{}.{forged_payload}.{}",
+            parts.next().unwrap(),
+            parts.nth(1).unwrap()
+        );
+        assert_eq!(
+            ErrorCode::InvalidToken,
+            signer.verify(&tampered).unwrap_err().code
+        );
+
+        let expired = signer.sign(&TokenClaims { exp: 1, ..claims });
+        assert_eq!(
+            ErrorCode::ExpiredToken,
+            signer.verify(&expired).unwrap_err().code
+        );
+
+        assert_eq!(
+            ErrorCode::UnparsableToken,
+            signer.verify(&access_token.to_string()).unwrap_err().code
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! TypeScript definitions of the wire types, for the frontend.
+//! They are rendered from the same JSON Schemas that back `/openapi.json`, and checked in at
+//! [`DTS_PATH`]; run `cargo run --bin gen_dts` after changing any of the types.
+
+use std::fmt::Write;
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{InstanceType, Schema, SchemaObject, SingleOrVec},
+    JsonSchema,
+};
+
+use crate::types::{
+    AfterHalfAcceptanceMessageStruct, ChatContentStruct, Ciurl, MainMessage, MainMessageStruct,
+    MoveToBePolled, MsgChatPoll, MsgRandomEntry, MsgVsCpuEntry, MsgWithAccessToken, MsgWithRoomId,
+    NonTamMoveDotData, RetAfterHalfAcceptance, RetChatPoll, RetGameRecord, RetInfAfterStep,
+    RetInfPoll, RetLegalMoves, RetLiveRooms, RetMainPoll, RetNormalMove, RetRandomCancel,
+    RetRandomEntry, RetRandomPoll, RetRoomState, RetSendChat, RetSpectateEntry, RetSpectatorPoll,
+    RetTaXot, RetTyMok, RetUnlistRoom, RetValidate, RetVsCpuEntry, RetWhetherTyMokPoll,
+    TamMoveInternal, WhoGoesFirst,
+};
+
+pub const DTS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/bindings/cerke_online.d.ts");
+
+const INDENT: &str = "    ";
+
+fn add<T: JsonSchema>(gen: &mut SchemaGenerator) {
+    gen.subschema_for::<T>();
+}
+
+/// Renders every exported type, along with everything they refer to, in alphabetical order.
+#[must_use]
+pub fn generate() -> String {
+    let mut gen = SchemaSettings::draft07().into_generator();
+
+    add::<MainMessage>(&mut gen);
+    add::<MainMessageStruct>(&mut gen);
+    add::<AfterHalfAcceptanceMessageStruct>(&mut gen);
+    add::<MoveToBePolled>(&mut gen);
+    add::<NonTamMoveDotData>(&mut gen);
+    add::<TamMoveInternal>(&mut gen);
+    add::<Ciurl>(&mut gen);
+    add::<WhoGoesFirst>(&mut gen);
+    add::<MsgWithAccessToken>(&mut gen);
+    add::<MsgWithRoomId>(&mut gen);
+    add::<MsgRandomEntry>(&mut gen);
+    add::<MsgVsCpuEntry>(&mut gen);
+    add::<MsgChatPoll>(&mut gen);
+    add::<ChatContentStruct>(&mut gen);
+
+    add::<RetTyMok>(&mut gen);
+    add::<RetTaXot>(&mut gen);
+    add::<RetInfAfterStep>(&mut gen);
+    add::<RetNormalMove>(&mut gen);
+    add::<RetValidate>(&mut gen);
+    add::<RetAfterHalfAcceptance>(&mut gen);
+    add::<RetRandomEntry>(&mut gen);
+    add::<RetVsCpuEntry>(&mut gen);
+    add::<RetRandomPoll>(&mut gen);
+    add::<RetRandomCancel>(&mut gen);
+    add::<RetWhetherTyMokPoll>(&mut gen);
+    add::<RetMainPoll>(&mut gen);
+    add::<RetInfPoll>(&mut gen);
+    add::<RetSpectateEntry>(&mut gen);
+    add::<RetSpectatorPoll>(&mut gen);
+    add::<RetGameRecord>(&mut gen);
+    add::<RetLiveRooms>(&mut gen);
+    add::<RetUnlistRoom>(&mut gen);
+    add::<RetRoomState>(&mut gen);
+    add::<RetLegalMoves>(&mut gen);
+    add::<RetSendChat>(&mut gen);
+    add::<RetChatPoll>(&mut gen);
+
+    let mut out = String::from(
+        "// Generated by `cargo run --bin gen_dts` from the Rust types. Do not edit by hand.\n",
+    );
+    for (name, schema) in gen.definitions() {
+        out.push('\n');
+        if let Schema::Object(SchemaObject {
+            metadata: Some(metadata),
+            ..
+        }) = schema
+        {
+            if let Some(description) = &metadata.description {
+                out.push_str(&doc_comment(description, ""));
+            }
+        }
+        writeln!(out, "export type {name} = {};", ts_type(schema, 0)).unwrap();
+    }
+    out
+}
+
+fn doc_comment(description: &str, indent: &str) -> String {
+    if !description.contains('\n') {
+        return format!("{indent}/** {description} */\n");
+    }
+    let mut out = format!("{indent}/**\n");
+    for line in description.lines() {
+        writeln!(out, "{indent} * {line}").unwrap();
+    }
+    writeln!(out, "{indent} */").unwrap();
+    out
+}
+
+fn parenthesize(ts: String) -> String {
+    if ts.contains(" | ") || ts.contains(" & ") {
+        format!("({ts})")
+    } else {
+        ts
+    }
+}
+
+fn ts_type(schema: &Schema, depth: usize) -> String {
+    match schema {
+        Schema::Bool(true) => "unknown".to_owned(),
+        Schema::Bool(false) => "never".to_owned(),
+        Schema::Object(obj) => ts_type_of_object(obj, depth),
+    }
+}
+
+fn ts_type_of_object(obj: &SchemaObject, depth: usize) -> String {
+    if let Some(reference) = &obj.reference {
+        return reference.rsplit('/').next().unwrap_or(reference).to_owned();
+    }
+
+    let mut parts = vec![];
+    if let Some(values) = &obj.enum_values {
+        parts.push(
+            values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" | "),
+        );
+    } else if let Some(value) = &obj.const_value {
+        parts.push(value.to_string());
+    } else if let Some(instance_type) = &obj.instance_type {
+        let types: Vec<InstanceType> = match instance_type {
+            SingleOrVec::Single(t) => vec![**t],
+            SingleOrVec::Vec(ts) => ts.clone(),
+        };
+        parts.push(
+            types
+                .into_iter()
+                .map(|t| ts_type_of_instance(t, obj, depth))
+                .collect::<Vec<_>>()
+                .join(" | "),
+        );
+    } else if obj.object.is_some() {
+        parts.push(ts_type_of_instance(InstanceType::Object, obj, depth));
+    }
+
+    if let Some(subschemas) = &obj.subschemas {
+        for alternatives in [&subschemas.one_of, &subschemas.any_of]
+            .into_iter()
+            .flatten()
+        {
+            parts.push(
+                alternatives
+                    .iter()
+                    .map(|s| ts_type(s, depth))
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            );
+        }
+        if let Some(all_of) = &subschemas.all_of {
+            parts.extend(all_of.iter().map(|s| ts_type(s, depth)));
+        }
+    }
+
+    match parts.len() {
+        0 => "unknown".to_owned(),
+        1 => parts.pop().unwrap_or_default(),
+        _ => parts
+            .into_iter()
+            .map(parenthesize)
+            .collect::<Vec<_>>()
+            .join(" & "),
+    }
+}
+
+fn ts_type_of_instance(instance_type: InstanceType, obj: &SchemaObject, depth: usize) -> String {
+    match instance_type {
+        InstanceType::Null => "null".to_owned(),
+        InstanceType::Boolean => "boolean".to_owned(),
+        InstanceType::Integer | InstanceType::Number => "number".to_owned(),
+        InstanceType::String => "string".to_owned(),
+        InstanceType::Array => match obj.array.as_ref().and_then(|array| array.items.as_ref()) {
+            None => "unknown[]".to_owned(),
+            Some(SingleOrVec::Single(item)) => {
+                format!("{}[]", parenthesize(ts_type(item, depth)))
+            }
+            Some(SingleOrVec::Vec(items)) => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(|item| ts_type(item, depth))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        InstanceType::Object => {
+            let Some(object) = &obj.object else {
+                return "Record<string, unknown>".to_owned();
+            };
+            if object.properties.is_empty() {
+                return match &object.additional_properties {
+                    Some(value) => format!("Record<string, {}>", ts_type(value, depth)),
+                    None => "Record<string, never>".to_owned(),
+                };
+            }
+            let indent = INDENT.repeat(depth + 1);
+            let mut out = String::from("{\n");
+            for (key, value) in &object.properties {
+                if let Schema::Object(SchemaObject {
+                    metadata: Some(metadata),
+                    ..
+                }) = value
+                {
+                    if let Some(description) = &metadata.description {
+                        out.push_str(&doc_comment(description, &indent));
+                    }
+                }
+                let optional = if object.required.contains(key) {
+                    ""
+                } else {
+                    "?"
+                };
+                writeln!(
+                    out,
+                    "{indent}{key}{optional}: {};",
+                    ts_type(value, depth + 1)
+                )
+                .unwrap();
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push('}');
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, DTS_PATH};
+
+    #[test]
+    fn dts_is_up_to_date() {
+        let checked_in = std::fs::read_to_string(DTS_PATH).unwrap_or_default();
+        assert!(
+            checked_in == generate(),
+            "{DTS_PATH} is out of date; run `cargo run --bin gen_dts` to regenerate it"
+        );
+    }
+
+    #[test]
+    fn every_ret_enum_is_exported() {
+        let generated = generate();
+        for source in [
+            include_str!("types/message.rs"),
+            include_str!("types/chat.rs"),
+        ] {
+            for line in source.lines() {
+                if let Some(name) = line
+                    .strip_prefix("pub enum ")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .filter(|name| name.starts_with("Ret"))
+                {
+                    assert!(
+                        generated.contains(&format!("export type {name} = ")),
+                        "`{name}` is not exported to TypeScript"
+                    );
+                }
+            }
+        }
+    }
+}